@@ -0,0 +1,326 @@
+use std::{collections::VecDeque, f32::consts::PI};
+
+use bevy::{prelude::*, transform::TransformSystem, utils::HashMap};
+
+use crate::{
+    common::{
+        components::{Health, Position, RenderLayer, Renderable},
+        Vec2Int, HEIGHT, WIDTH,
+    },
+    map_generator::{viewshed::MapVisibility, Map},
+};
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AnimationSettings>()
+            .add_event::<Lunge>()
+            .add_systems(
+                PostUpdate,
+                (
+                    queue_movement,
+                    queue_lunges,
+                    show_damage,
+                    play_animations,
+                    play_hit_flashes,
+                    float_damage_numbers,
+                )
+                    .chain()
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Moves further than this many tiles at once are teleports and are not tweened.
+const MAX_TWEEN_DISTANCE: f32 = 1.5;
+/// How far towards its target a lunge reaches, in tiles.
+const LUNGE_REACH: f32 = 0.4;
+const FLASH_COLOR: Color = Color::RED;
+/// How far damage numbers rise before they are gone, in pixels.
+const DAMAGE_NUMBER_RISE: f32 = 12.0;
+
+#[derive(Resource)]
+pub struct AnimationSettings {
+    /// Without animations everything snaps to its tile and the game never waits.
+    pub enabled: bool,
+    pub move_seconds: f32,
+    pub lunge_seconds: f32,
+    pub flash_seconds: f32,
+    pub damage_number_seconds: f32,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            move_seconds: 0.08,
+            lunge_seconds: 0.12,
+            flash_seconds: 0.25,
+            damage_number_seconds: 0.8,
+        }
+    }
+}
+
+/// Makes an entity jump towards a neighbouring tile and back, for attacks and other actions
+/// that hit something without moving there.
+#[derive(Event)]
+pub struct Lunge {
+    pub entity: Entity,
+    pub toward: Vec2Int,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Animation {
+    Move { from: Vec3, to: Vec3 },
+    Lunge { from: Vec3, offset: Vec3 },
+}
+
+impl Animation {
+    fn seconds(&self, settings: &AnimationSettings) -> f32 {
+        match self {
+            Animation::Move { .. } => settings.move_seconds,
+            Animation::Lunge { .. } => settings.lunge_seconds,
+        }
+    }
+
+    /// Where the animation is at `t`, which runs from 0 to 1.
+    fn translation(&self, t: f32) -> Vec3 {
+        match *self {
+            Animation::Move { from, to } => from.lerp(to, t * (2.0 - t)),
+            Animation::Lunge { from, offset } => from + offset * (PI * t).sin(),
+        }
+    }
+}
+
+/// The animations of an entity, played one after the other. Entities without one snap to
+/// their tile.
+#[derive(Component, Default)]
+pub struct AnimationQueue {
+    animations: VecDeque<Animation>,
+    elapsed: f32,
+    /// Where the entity comes to rest once the queue is played.
+    rest: Option<Vec3>,
+}
+
+impl AnimationQueue {
+    pub fn is_empty(&self) -> bool {
+        self.animations.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.animations.clear();
+        self.elapsed = 0.0;
+    }
+}
+
+/// Tints the sprite after it was hurt and fades back to its own color.
+#[derive(Component)]
+pub struct HitFlash {
+    elapsed: f32,
+}
+
+#[derive(Component)]
+struct DamageNumber {
+    elapsed: f32,
+    from: Vec3,
+}
+
+/// Run condition that holds the next turn back until every move, lunge and flash in view is
+/// played. Nobody waits for what happens out of sight.
+pub fn animations_finished(
+    settings: Res<AnimationSettings>,
+    map: Res<Map>,
+    fog: Res<MapVisibility>,
+    queues: Query<(&AnimationQueue, &Position)>,
+    flashes: Query<&Position, With<HitFlash>>,
+) -> bool {
+    let in_view = |position: &Position| fog.is_visible(&map, position.0);
+    !settings.enabled
+        || (queues
+            .iter()
+            .all(|(queue, position)| queue.is_empty() || !in_view(position))
+            && !flashes.iter().any(in_view))
+}
+
+fn queue_movement(
+    settings: Res<AnimationSettings>,
+    mut animated: Query<(
+        Entity,
+        &mut Transform,
+        Ref<Position>,
+        &Renderable,
+        &mut AnimationQueue,
+    )>,
+) {
+    for (entity, mut transform, position, renderable, mut queue) in &mut animated {
+        // Sprites only get their transform a frame after they are spawned, so the first
+        // placement cannot wait for the position to change.
+        if queue.rest.is_some() && !position.is_changed() {
+            continue;
+        }
+        let to = position.to_world(renderable.layer.z_of(entity));
+        let Some(from) = queue.rest else {
+            transform.translation = to;
+            queue.rest = Some(to);
+            continue;
+        };
+        if from == to {
+            continue;
+        }
+        let tiles = ((to - from).truncate() / Vec2::new(WIDTH, HEIGHT)).length();
+        if !settings.enabled || tiles > MAX_TWEEN_DISTANCE {
+            queue.clear();
+            transform.translation = to;
+        } else {
+            queue.animations.push_back(Animation::Move { from, to });
+        }
+        queue.rest = Some(to);
+    }
+}
+
+fn queue_lunges(
+    settings: Res<AnimationSettings>,
+    mut lunges: EventReader<Lunge>,
+    mut animated: Query<(&Position, &mut AnimationQueue)>,
+) {
+    for lunge in lunges.read() {
+        let Ok((position, mut queue)) = animated.get_mut(lunge.entity) else {
+            continue;
+        };
+        if !settings.enabled {
+            continue;
+        }
+        let Some(from) = queue.rest else {
+            continue;
+        };
+        let offset = (lunge.toward.to_world(0.0) - position.to_world(0.0)) * LUNGE_REACH;
+        queue
+            .animations
+            .push_back(Animation::Lunge { from, offset });
+    }
+}
+
+/// Flashes everything in view that lost health and lets the damage float up from it.
+fn show_damage(
+    settings: Res<AnimationSettings>,
+    map: Option<Res<Map>>,
+    fog: Option<Res<MapVisibility>>,
+    mut last_health: Local<HashMap<Entity, i32>>,
+    hurt: Query<(Entity, &Health, &Position, Has<Renderable>), Changed<Health>>,
+    mut commands: Commands,
+) {
+    for (entity, health, position, renderable) in &hurt {
+        let Some(last) = last_health.insert(entity, health.current) else {
+            continue;
+        };
+        let damage = last - health.current;
+        if !settings.enabled || damage <= 0 {
+            continue;
+        }
+        let (Some(map), Some(fog)) = (&map, &fog) else {
+            continue;
+        };
+        if !fog.is_visible(map, position.0) {
+            continue;
+        }
+        if renderable {
+            commands.entity(entity).insert(HitFlash { elapsed: 0.0 });
+        }
+        let from = position.to_world(RenderLayer::Effects.z()) + Vec3::Y * HEIGHT / 2.0;
+        commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(
+                    damage.to_string(),
+                    TextStyle {
+                        font_size: 10.0,
+                        color: FLASH_COLOR,
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_translation(from),
+                ..default()
+            })
+            .insert(Name::from("Damage"))
+            .insert(DamageNumber { elapsed: 0.0, from });
+    }
+}
+
+pub fn play_animations(
+    time: Res<Time>,
+    settings: Res<AnimationSettings>,
+    mut animated: Query<(&mut Transform, &mut AnimationQueue)>,
+) {
+    for (mut transform, mut queue) in &mut animated {
+        let Some(animation) = queue.animations.front().copied() else {
+            continue;
+        };
+        queue.elapsed += time.delta_seconds();
+        let t = (queue.elapsed / animation.seconds(&settings)).min(1.0);
+        transform.translation = animation.translation(t);
+        if t >= 1.0 {
+            queue.animations.pop_front();
+            queue.elapsed = 0.0;
+        }
+    }
+}
+
+fn play_hit_flashes(
+    time: Res<Time>,
+    settings: Res<AnimationSettings>,
+    mut flashing: Query<(Entity, &mut HitFlash, &Renderable, &mut TextureAtlasSprite)>,
+    mut commands: Commands,
+) {
+    for (entity, mut flash, renderable, mut sprite) in &mut flashing {
+        flash.elapsed += time.delta_seconds();
+        let t = (flash.elapsed / settings.flash_seconds).min(1.0);
+        let [r, g, b, a] = FLASH_COLOR.as_rgba_f32();
+        let [r2, g2, b2, a2] = renderable.fg.as_rgba_f32();
+        sprite.color = Color::rgba(
+            r + (r2 - r) * t,
+            g + (g2 - g) * t,
+            b + (b2 - b) * t,
+            a + (a2 - a) * t,
+        );
+        if t >= 1.0 {
+            commands.entity(entity).remove::<HitFlash>();
+        }
+    }
+}
+
+fn float_damage_numbers(
+    time: Res<Time>,
+    settings: Res<AnimationSettings>,
+    mut numbers: Query<(Entity, &mut DamageNumber, &mut Transform, &mut Text)>,
+    mut commands: Commands,
+) {
+    for (entity, mut number, mut transform, mut text) in &mut numbers {
+        number.elapsed += time.delta_seconds();
+        let t = (number.elapsed / settings.damage_number_seconds).min(1.0);
+        transform.translation = number.from + Vec3::Y * DAMAGE_NUMBER_RISE * t;
+        for section in &mut text.sections {
+            section.style.color.set_a(1.0 - t);
+        }
+        if t >= 1.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[test]
+fn test_animation() {
+    let from = Vec3::new(0.0, 0.0, 1.0);
+    let to = Vec3::new(WIDTH, 0.0, 1.0);
+    let step = Animation::Move { from, to };
+    assert_eq!(step.translation(0.0), from);
+    assert_eq!(step.translation(1.0), to);
+    assert!(step.translation(0.5).x > WIDTH / 2.0);
+
+    let lunge = Animation::Lunge {
+        from,
+        offset: (to - from) * LUNGE_REACH,
+    };
+    assert_eq!(lunge.translation(0.0), from);
+    assert!((lunge.translation(0.5) - from).x > 0.0);
+    assert!((lunge.translation(1.0) - from).length() < 1e-4);
+}
@@ -0,0 +1,218 @@
+use bevy::{
+    input::mouse::MouseWheel, prelude::*, transform::TransformSystem, window::PrimaryWindow,
+};
+
+use crate::{
+    animation::play_animations,
+    common::{HEIGHT, WIDTH},
+    map_generator::Map,
+    player::Player,
+    MainCamera,
+};
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraSettings>()
+            .add_systems(Update, zoom_camera)
+            .add_systems(
+                PostUpdate,
+                follow_player
+                    .after(play_animations)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Tweaks for how the map camera zooms and follows the player. Zoom is the camera scale, so
+/// smaller values are closer to the map.
+#[derive(Resource)]
+pub struct CameraSettings {
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// Factor the zoom changes by for every wheel notch or key press.
+    pub zoom_step: f32,
+    pub zoom_in: Vec<KeyCode>,
+    pub zoom_out: Vec<KeyCode>,
+    /// Toggles between following the player and showing the whole map.
+    pub fit_map: KeyCode,
+    /// Glide after the player instead of sticking to them.
+    pub smooth_follow: bool,
+    /// How quickly the camera catches up when following smoothly, per second.
+    pub follow_speed: f32,
+    /// Room the player can move around the center of the screen in before the camera follows,
+    /// in tiles.
+    pub deadzone: Vec2,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            min_zoom: 0.25,
+            max_zoom: 1.0,
+            zoom_step: 1.25,
+            zoom_in: vec![KeyCode::Equals, KeyCode::NumpadAdd],
+            zoom_out: vec![KeyCode::Minus, KeyCode::NumpadSubtract],
+            fit_map: KeyCode::Tab,
+            smooth_follow: true,
+            follow_speed: 8.0,
+            deadzone: Vec2::new(4.0, 2.0),
+        }
+    }
+}
+
+/// How the map camera looks at the map.
+#[derive(Component)]
+pub struct CameraView {
+    pub zoom: f32,
+    /// Shows the whole map at once, ignoring the player and the zoom limits.
+    pub fit_map: bool,
+}
+
+impl Default for CameraView {
+    fn default() -> Self {
+        Self {
+            zoom: 0.5,
+            fit_map: false,
+        }
+    }
+}
+
+fn zoom_camera(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut wheel: EventReader<MouseWheel>,
+    settings: Res<CameraSettings>,
+    mut views: Query<&mut CameraView>,
+) {
+    // Only the direction of the wheel counts, mice and touchpads scroll in different units.
+    let mut steps: i32 = wheel.read().map(|event| event.y.signum() as i32).sum();
+    if keyboard_input.any_just_pressed(settings.zoom_in.iter().copied()) {
+        steps += 1;
+    }
+    if keyboard_input.any_just_pressed(settings.zoom_out.iter().copied()) {
+        steps -= 1;
+    }
+    for mut view in &mut views {
+        if keyboard_input.just_pressed(settings.fit_map) {
+            view.fit_map = !view.fit_map;
+        }
+        if steps != 0 {
+            view.fit_map = false;
+            view.zoom = (view.zoom / settings.zoom_step.powi(steps))
+                .clamp(settings.min_zoom, settings.max_zoom);
+        }
+    }
+}
+
+fn follow_player(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    map: Option<Res<Map>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    players: Query<&Transform, (With<Player>, Without<MainCamera>)>,
+    mut cameras: Query<(&mut Transform, &CameraView), With<MainCamera>>,
+) {
+    let window = windows
+        .get_single()
+        .map(|window| Vec2::new(window.width(), window.height()))
+        .ok();
+    // Tile centers sit on multiples of the tile size, so the map reaches half a tile further.
+    let bounds = map.map(|map| {
+        let tile = Vec2::new(WIDTH, HEIGHT);
+        Rect::from_corners(
+            -tile / 2.0,
+            Vec2::new(map.width as f32, map.height as f32) * tile - tile / 2.0,
+        )
+    });
+    for (mut camera, view) in &mut cameras {
+        let current = camera.translation.truncate();
+        let (zoom, center) = match (view.fit_map, bounds, window) {
+            (true, Some(bounds), Some(window)) => (fit_zoom(bounds, window), bounds.center()),
+            _ => {
+                let Ok(player) = players.get_single() else {
+                    continue;
+                };
+                let target = follow(
+                    current,
+                    player.translation.truncate(),
+                    settings.deadzone * Vec2::new(WIDTH, HEIGHT) / 2.0,
+                );
+                // Anything off screen, like the start of the game or a teleport, is jumped to.
+                let on_screen = window.is_some_and(|window| {
+                    (target - current)
+                        .abs()
+                        .cmple(window * view.zoom / 2.0)
+                        .all()
+                });
+                let center = if settings.smooth_follow && on_screen {
+                    let blend = 1.0 - (-settings.follow_speed * time.delta_seconds()).exp();
+                    current.lerp(target, blend)
+                } else {
+                    target
+                };
+                (view.zoom, center)
+            }
+        };
+        let center = match (bounds, window) {
+            (Some(bounds), Some(window)) => clamp_to_map(center, window * zoom / 2.0, bounds),
+            _ => center,
+        };
+        // The camera keeps its own depth, above every render layer.
+        camera.translation = center.extend(camera.translation.z);
+        camera.scale = Vec3::new(zoom, zoom, 1.0);
+    }
+}
+
+/// Moves the camera just far enough that the target is within `half_deadzone` of its center.
+fn follow(camera: Vec2, target: Vec2, half_deadzone: Vec2) -> Vec2 {
+    let offset = target - camera;
+    camera + offset - offset.clamp(-half_deadzone, half_deadzone)
+}
+
+/// Keeps the view inside the map, a map smaller than the view is centered instead.
+fn clamp_to_map(center: Vec2, half_view: Vec2, bounds: Rect) -> Vec2 {
+    let clamp_axis = |center: f32, half_view: f32, min: f32, max: f32| {
+        if max - min <= half_view * 2.0 {
+            (min + max) / 2.0
+        } else {
+            center.clamp(min + half_view, max - half_view)
+        }
+    };
+    Vec2::new(
+        clamp_axis(center.x, half_view.x, bounds.min.x, bounds.max.x),
+        clamp_axis(center.y, half_view.y, bounds.min.y, bounds.max.y),
+    )
+}
+
+/// The zoom at which the whole map just fits into the window.
+fn fit_zoom(bounds: Rect, window: Vec2) -> f32 {
+    let zoom = bounds.size() / window;
+    zoom.x.max(zoom.y)
+}
+
+#[test]
+fn test_camera_bounds() {
+    let bounds = Rect::new(0.0, 0.0, 100.0, 50.0);
+    let half_view = Vec2::new(20.0, 40.0);
+    // Wide enough to scroll sideways, too short to scroll up and down.
+    assert_eq!(
+        clamp_to_map(Vec2::new(5.0, 0.0), half_view, bounds),
+        Vec2::new(20.0, 25.0)
+    );
+    assert_eq!(
+        clamp_to_map(Vec2::new(50.0, 0.0), half_view, bounds),
+        Vec2::new(50.0, 25.0)
+    );
+    assert_eq!(fit_zoom(bounds, Vec2::new(50.0, 50.0)), 2.0);
+
+    let deadzone = Vec2::new(10.0, 10.0);
+    assert_eq!(
+        follow(Vec2::ZERO, Vec2::new(5.0, -5.0), deadzone),
+        Vec2::ZERO
+    );
+    assert_eq!(
+        follow(Vec2::ZERO, Vec2::new(15.0, 0.0), deadzone),
+        Vec2::new(5.0, 0.0)
+    );
+}
@@ -4,3 +4,103 @@ use super::Vec2Int;
 
 #[derive(Component, Deref, DerefMut)]
 pub struct Position(pub Vec2Int);
+
+/// A sentence about what something is, for looking at it.
+#[derive(Component)]
+pub struct Description(pub &'static str);
+
+#[derive(Component)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32,
+}
+
+/// What a creature can get through besides open floor.
+#[derive(Component, Clone, Copy, Default)]
+pub struct Mobility {
+    /// Closed doors are opened by bumping into them instead of blocking the way.
+    pub opens_doors: bool,
+    /// Walls and other diggable tiles are worn down by bumping into them, see
+    /// [`TileProperties::dig_turns`](super::TileProperties::dig_turns).
+    pub digs: bool,
+}
+
+impl Mobility {
+    /// For the player's automatic movement: digging is only ever done on purpose, by bumping
+    /// into walls.
+    pub fn without_digging(self) -> Self {
+        Self {
+            digs: false,
+            ..self
+        }
+    }
+}
+
+/// Lights up the tiles around whatever carries it, see
+/// [`LightMap`](crate::map_generator::lighting::LightMap).
+#[derive(Component, Clone, Copy)]
+pub struct LightSource {
+    /// Tiles further away than this stay dark.
+    pub radius: f32,
+}
+
+/// What an entity is drawn above and below of, layers further down this list are drawn on top.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderLayer {
+    Terrain,
+    Decals,
+    Items,
+    Creatures,
+    Player,
+    Effects,
+    Overlay,
+}
+
+impl RenderLayer {
+    /// Room between two layers, the entities on a layer are spread over half of it.
+    const DEPTH: f32 = 10.0;
+    /// Entity indices that get a depth of their own, far more than are ever alive at once. The
+    /// steps between them stay well above the precision of an `f32` at the top layer.
+    const SLOTS: u32 = 1 << 16;
+
+    pub fn z(self) -> f32 {
+        self as u8 as f32 * Self::DEPTH
+    }
+
+    /// Gives every entity on the layer a depth of its own, so overlapping entities are always
+    /// drawn in the same order instead of flickering.
+    pub fn z_of(self, entity: Entity) -> f32 {
+        assert!(
+            entity.index() < Self::SLOTS,
+            "entity {entity:?} is past the depths of a render layer"
+        );
+        self.z() + entity.index() as f32 * Self::DEPTH / 2.0 / Self::SLOTS as f32
+    }
+}
+
+/// How an entity looks as a cell of the character grid, whichever renderer draws it.
+#[derive(Component, Clone, Copy)]
+pub struct Renderable {
+    pub glyph: char,
+    pub fg: Color,
+    /// Cells without a background let whatever is below show through.
+    pub bg: Option<Color>,
+    pub layer: RenderLayer,
+}
+
+#[test]
+fn test_render_layers() {
+    let first = Entity::from_raw(0);
+    let last = Entity::from_raw(RenderLayer::SLOTS - 1);
+    assert_eq!(
+        RenderLayer::Creatures.z_of(first),
+        RenderLayer::Creatures.z()
+    );
+    assert!(RenderLayer::Creatures.z_of(last) > RenderLayer::Creatures.z_of(first));
+    assert!(
+        RenderLayer::Overlay.z_of(last)
+            > RenderLayer::Overlay.z_of(Entity::from_raw(RenderLayer::SLOTS - 2))
+    );
+    assert!(RenderLayer::Creatures.z_of(last) < RenderLayer::Player.z());
+    assert!(RenderLayer::Player.z() < RenderLayer::Effects.z());
+}
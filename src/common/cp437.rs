@@ -0,0 +1,47 @@
+//! The glyph atlas is laid out in code page 437 order. Printable ASCII sits at the same
+//! indices, the line and block glyphs above it are looked up by their Unicode counterparts.
+
+/// Box drawing, block and shading glyphs, by their index in the atlas.
+const EXTENDED: [(char, usize); 22] = [
+    ('│', 179),
+    ('┤', 180),
+    ('┐', 191),
+    ('└', 192),
+    ('┴', 193),
+    ('┬', 194),
+    ('├', 195),
+    ('─', 196),
+    ('┼', 197),
+    ('┘', 217),
+    ('┌', 218),
+    ('█', 219),
+    ('▄', 220),
+    ('▌', 221),
+    ('▐', 222),
+    ('▀', 223),
+    ('░', 176),
+    ('▒', 177),
+    ('▓', 178),
+    ('·', 250),
+    ('■', 254),
+    ('►', 16),
+];
+
+/// Atlas index of a glyph. Glyphs the code page does not have are drawn as `?`.
+pub fn to_index(glyph: char) -> usize {
+    match glyph {
+        ' '..='~' => glyph as usize,
+        _ => EXTENDED
+            .iter()
+            .find(|(extended, _)| *extended == glyph)
+            .map_or('?' as usize, |(_, index)| *index),
+    }
+}
+
+#[test]
+fn test_to_index() {
+    assert_eq!(to_index('@'), 64);
+    assert_eq!(to_index('█'), 219);
+    assert_eq!(to_index('┌'), 218);
+    assert_eq!(to_index('é'), '?' as usize);
+}
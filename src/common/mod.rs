@@ -1,29 +1,27 @@
 use bevy::prelude::*;
 
 pub mod components;
+pub mod cp437;
+pub mod pathfinding;
 pub mod rect;
 pub mod resources;
+pub mod spawns;
 pub mod states;
+pub mod tile;
 pub mod vec2int;
 
-pub use rect::*;
+pub use tile::*;
 pub use vec2int::*;
 
 pub const WIDTH: f32 = 16.0;
 pub const HEIGHT: f32 = 16.0;
 
-#[derive(Clone, Copy, PartialEq)]
-pub enum TileType {
-    Wall,
-    Floor,
-}
-
 pub trait ToWorld {
-    fn to_world(&self) -> Vec3;
+    fn to_world(&self, z: f32) -> Vec3;
 }
 
 impl ToWorld for (i32, i32) {
-    fn to_world(&self) -> Vec3 {
-        Vec3::new(self.0 as f32 * WIDTH, self.1 as f32 * HEIGHT, 1.0)
+    fn to_world(&self, z: f32) -> Vec3 {
+        Vec3::new(self.0 as f32 * WIDTH, self.1 as f32 * HEIGHT, z)
     }
 }
\ No newline at end of file
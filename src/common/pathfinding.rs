@@ -0,0 +1,332 @@
+use std::{collections::{HashMap, BinaryHeap, VecDeque}, cmp::Ordering};
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{common::{components::Mobility, resources::MovementRules, Vec2Int}, map_generator::Map};
+
+const STRAIGHT_COST: i32 = 10;
+const DIAGONAL_COST: i32 = 14;
+/// Tiles to avoid are only walked over when there is no other way.
+const AVOID_COST: i32 = 50 * STRAIGHT_COST;
+
+#[derive(Component, Debug, PartialEq)]
+pub struct Path {
+    pub waypoints: VecDeque<Vec2Int>,
+}
+
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct Node {
+    cost: i32,
+    position: Vec2Int,
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Path {
+    pub fn calculate(
+        start: Vec2Int,
+        target: Vec2Int,
+        map: &Map,
+        rules: &MovementRules,
+        mobility: Mobility,
+        avoid: &HashSet<Vec2Int>,
+    ) -> Option<Path> {
+        Self::search(
+            start,
+            map,
+            rules,
+            mobility,
+            avoid,
+            |_| true,
+            |position| position.octile_distance(&target, STRAIGHT_COST, DIAGONAL_COST),
+            |position| position == target,
+        )
+    }
+
+    /// Like [`Path::calculate`], but never steps onto a tile for which `can_enter` is false.
+    pub fn calculate_within(
+        start: Vec2Int,
+        target: Vec2Int,
+        map: &Map,
+        rules: &MovementRules,
+        mobility: Mobility,
+        avoid: &HashSet<Vec2Int>,
+        can_enter: impl Fn(Vec2Int) -> bool,
+    ) -> Option<Path> {
+        Self::search(
+            start,
+            map,
+            rules,
+            mobility,
+            avoid,
+            can_enter,
+            |position| position.octile_distance(&target, STRAIGHT_COST, DIAGONAL_COST),
+            |position| position == target,
+        )
+    }
+
+    /// Finds the path to the closest reachable tile for which `is_goal` holds.
+    pub fn to_nearest(
+        start: Vec2Int,
+        map: &Map,
+        rules: &MovementRules,
+        mobility: Mobility,
+        avoid: &HashSet<Vec2Int>,
+        is_goal: impl Fn(Vec2Int) -> bool,
+    ) -> Option<Path> {
+        Self::search(start, map, rules, mobility, avoid, |_| true, |_| 0, is_goal)
+    }
+
+    fn search(
+        start: Vec2Int,
+        map: &Map,
+        rules: &MovementRules,
+        mobility: Mobility,
+        avoid: &HashSet<Vec2Int>,
+        can_enter: impl Fn(Vec2Int) -> bool,
+        heuristic: impl Fn(Vec2Int) -> i32,
+        is_goal: impl Fn(Vec2Int) -> bool,
+    ) -> Option<Path> {
+        let mut heads = BinaryHeap::new();
+        heads.push(Node {
+            cost: 0,
+            position: start,
+        });
+    
+        let mut came_from: HashMap<Vec2Int, Option<Vec2Int>> = HashMap::new();
+        came_from.insert(start, None);
+        let mut cost_so_far = HashMap::new();
+        cost_so_far.insert(start, 0);
+        let mut target = None;
+    
+        while let Some(head) = heads.pop() {
+            if is_goal(head.position) {
+                target = Some(head.position);
+                break;
+            }
+            for direction in Vec2Int::DIRECTIONS {
+                let next = head.position + direction;
+                if !map.can_pass(head.position, next, rules, mobility) || !can_enter(next) {
+                    continue;
+                }
+                let mut new_cost = cost_so_far.get(&head.position).unwrap() + cost(map, head.position, next);
+                if avoid.contains(&next) {
+                    new_cost += AVOID_COST;
+                }
+                if !cost_so_far.contains_key(&next) || new_cost < *cost_so_far.get(&next).unwrap() {
+                    cost_so_far.insert(next, new_cost);
+                    let priority = new_cost + heuristic(next);
+                    heads.push(Node {
+                        cost: priority,
+                        position: next,
+                    });
+                    came_from.insert(next, Some(head.position));
+                }
+            }
+        }
+    
+        let mut waypoints = Vec::new();
+        let mut current = target?;
+        while let Some(previous) = came_from.get(&current) {
+            if let Some(p) = previous {
+                waypoints.push(current);
+                current = *p;
+            }
+            else {
+                break;
+            }
+        }
+    
+        if waypoints.is_empty() {
+            return None;
+        }
+        Some(Path {
+            waypoints: waypoints.iter().copied().rev().collect()
+        })
+    }
+}
+
+
+/// Scales the step by the move cost of the tile. Closed doors cost an extra turn to open, other
+/// blocking tiles on a path are dug through, which takes their dig turns.
+fn cost(map: &Map, from: Vec2Int, to: Vec2Int) -> i32 {
+    let idx = map.xy_idx(to.x, to.y);
+    let base = if (to - from).is_diagonal() {
+        DIAGONAL_COST
+    } else {
+        STRAIGHT_COST
+    };
+    let tile = map.tiles[idx];
+    let step = base * tile.properties().move_cost;
+    if !map.is_occupied(idx) {
+        step
+    } else if tile.is_door() {
+        step + STRAIGHT_COST
+    } else {
+        step + tile.properties().dig_turns.unwrap_or(0) * STRAIGHT_COST
+    }
+}
+
+#[test]
+fn test_pathfinding() {
+    let (map, markers) = Map::from_ascii(
+        "
+        ############
+        #s...#.....#
+        #....#..t..#
+        #..........#
+        ######.#####
+        #x.#.......#
+        ############",
+    )
+    .unwrap();
+    let [start, target, dead_end] = ['s', 't', 'x'].map(|marker| markers[&marker][0]);
+    let rules = MovementRules::default();
+    let path = Path::calculate(start, target, &map, &rules, Mobility::default(), &HashSet::new()).unwrap();
+    assert_eq!(path.waypoints.back(), Some(&target));
+    assert_eq!(path.waypoints.len(), 7);
+    let path = Path::calculate(start, dead_end, &map, &rules, Mobility::default(), &HashSet::new());
+    assert_eq!(path, None);
+}
+
+#[test]
+fn test_diagonal_squeeze() {
+    let (map, markers) = Map::from_ascii(
+        "
+        ####
+        #.t#
+        #s##
+        ####",
+    )
+    .unwrap();
+    let [start, target] = ['s', 't'].map(|marker| markers[&marker][0]);
+    let path = Path::calculate(start, target, &map, &MovementRules::default(), Mobility::default(), &HashSet::new()).unwrap();
+    assert_eq!(path.waypoints.len(), 1);
+
+    let (map, markers) = Map::from_ascii(
+        "
+        #####
+        ##t.#
+        #s#.#
+        #...#
+        #####",
+    )
+    .unwrap();
+    let [start, target] = ['s', 't'].map(|marker| markers[&marker][0]);
+    let path = Path::calculate(start, target, &map, &MovementRules::default(), Mobility::default(), &HashSet::new()).unwrap();
+    assert_eq!(path.waypoints.len(), 1);
+    let rules = MovementRules {
+        block_diagonal_squeeze: true,
+    };
+    let path = Path::calculate(start, target, &map, &rules, Mobility::default(), &HashSet::new()).unwrap();
+    assert_eq!(path.waypoints.len(), 3);
+}
+
+#[test]
+fn test_nearest() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #########
+        #...a...#
+        #.......#
+        #.s.....#
+        #########",
+    )
+    .unwrap();
+    let [start, goal] = ['s', 'a'].map(|marker| markers[&marker][0]);
+    let rules = MovementRules::default();
+    let path = Path::to_nearest(start, &map, &rules, Mobility::default(), &HashSet::new(), |tile| tile.x == 7 || tile == goal).unwrap();
+    assert_eq!(path.waypoints.back(), Some(&goal));
+    assert_eq!(path.waypoints.len(), 2);
+    assert_eq!(Path::to_nearest(start, &map, &rules, Mobility::default(), &HashSet::new(), |tile| tile.x == 0), None);
+}
+
+#[test]
+fn test_doors() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #######
+        #s.+.t#
+        #######",
+    )
+    .unwrap();
+    let [start, target] = ['s', 't'].map(|marker| markers[&marker][0]);
+    let rules = MovementRules::default();
+    assert_eq!(Path::calculate(start, target, &map, &rules, Mobility::default(), &HashSet::new()), None);
+    let mobility = Mobility {
+        opens_doors: true,
+        digs: false,
+    };
+    let path = Path::calculate(start, target, &map, &rules, mobility, &HashSet::new()).unwrap();
+    assert_eq!(path.waypoints.len(), 4);
+}
+
+#[test]
+fn test_move_cost() {
+    let (mut map, markers) = Map::from_ascii(
+        "
+        #######
+        #.....#
+        #s...t#
+        #######",
+    )
+    .unwrap();
+    let [start, target] = ['s', 't'].map(|marker| markers[&marker][0]);
+    for x in 2..=4 {
+        let idx = map.xy_idx(x, start.y);
+        map.tiles[idx] = crate::common::TileType::Water;
+    }
+    let rules = MovementRules::default();
+    let path = Path::calculate(start, target, &map, &rules, Mobility::default(), &HashSet::new()).unwrap();
+    assert!(path.waypoints.iter().all(|waypoint| waypoint.y != start.y || *waypoint == target));
+}
+
+#[test]
+fn test_digging() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #########
+        #s.#.#.t#
+        #########",
+    )
+    .unwrap();
+    let [start, target] = ['s', 't'].map(|marker| markers[&marker][0]);
+    let rules = MovementRules::default();
+    let digger = Mobility {
+        opens_doors: false,
+        digs: true,
+    };
+    let path = Path::calculate(start, target, &map, &rules, digger, &HashSet::new()).unwrap();
+    assert_eq!(path.waypoints.len(), 6);
+    assert!(path.waypoints.iter().all(|waypoint| waypoint.y == start.y));
+    assert_eq!(Path::calculate(start, target, &map, &rules, Mobility::default(), &HashSet::new()), None);
+}
+
+#[test]
+fn test_avoid() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #######
+        #.....#
+        #s.x.t#
+        #######",
+    )
+    .unwrap();
+    let [start, target, trap] = ['s', 't', 'x'].map(|marker| markers[&marker][0]);
+    let rules = MovementRules::default();
+    let avoid: HashSet<Vec2Int> = [trap].into_iter().collect();
+    let path = Path::calculate(start, target, &map, &rules, Mobility::default(), &avoid).unwrap();
+    assert!(!path.waypoints.contains(&trap));
+    let path = Path::calculate(start, trap, &map, &rules, Mobility::default(), &avoid).unwrap();
+    assert_eq!(path.waypoints.back(), Some(&trap));
+}
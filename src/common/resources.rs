@@ -1,6 +1,30 @@
 use bevy::prelude::*;
 
+use super::Vec2Int;
+
 #[derive(Resource)]
 pub struct CharsetAsset {
     pub atlas: Handle<TextureAtlas>,
 }
+
+/// Rules shared by everything that moves on the map.
+#[derive(Resource, Default)]
+pub struct MovementRules {
+    /// Forbid diagonal steps between two orthogonally adjacent walls.
+    pub block_diagonal_squeeze: bool,
+}
+
+/// What the keyboard is driving at the moment. Player input only runs while it is the game.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputFocus {
+    #[default]
+    Game,
+    Menu,
+    Look,
+    /// Picking a target, see [`Targeting`](crate::targeting::Targeting).
+    Target,
+}
+
+/// The grid cell under the mouse cursor, if the cursor is inside the window.
+#[derive(Resource, Default)]
+pub struct HoveredTile(pub Option<Vec2Int>);
@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+/// Something the map generator wants placed on the level once the map is built.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Spawn {
+    Monster(Monster),
+    Item(ItemKind),
+    Trap(TrapKind),
+    /// A fire bowl that lights up its surroundings.
+    Brazier,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Monster {
+    Goblin,
+    Orc,
+    Dwarf,
+    FireBeetle,
+}
+
+impl Monster {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Monster::Goblin => "Goblin",
+            Monster::Orc => "Orc",
+            Monster::Dwarf => "Dwarf",
+            Monster::FireBeetle => "Fire beetle",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Monster::Goblin => "A small, quick creature with a nose for loot.",
+            Monster::Orc => "A hulking brute, too impatient for door handles.",
+            Monster::Dwarf => "A stout miner who goes through walls, not around.",
+            Monster::FireBeetle => "A beetle whose shell glows like embers.",
+        }
+    }
+
+    pub fn glyph(&self) -> char {
+        match self {
+            Monster::Goblin => 'g',
+            Monster::Orc => 'o',
+            Monster::Dwarf => 'h',
+            Monster::FireBeetle => 'b',
+        }
+    }
+
+    /// Goblins and dwarves know their way around doors, orcs wait for someone to open them.
+    pub fn opens_doors(&self) -> bool {
+        match self {
+            Monster::Goblin | Monster::Dwarf => true,
+            Monster::Orc | Monster::FireBeetle => false,
+        }
+    }
+
+    /// Dwarves dig straight through walls to get where they want to be.
+    pub fn digs(&self) -> bool {
+        matches!(self, Monster::Dwarf)
+    }
+
+    /// Fire beetles glow and light up dark rooms as they crawl through them.
+    pub fn light_radius(&self) -> Option<f32> {
+        match self {
+            Monster::FireBeetle => Some(2.5),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Monster::Goblin => Color::rgb(1.0, 0.0, 0.0),
+            Monster::Orc => Color::rgb(0.2, 0.8, 0.2),
+            Monster::Dwarf => Color::rgb(0.9, 0.6, 0.3),
+            Monster::FireBeetle => Color::rgb(1.0, 0.5, 0.1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ItemKind {
+    Potion,
+    Gold,
+    Pickaxe,
+}
+
+impl ItemKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ItemKind::Potion => "Potion",
+            ItemKind::Gold => "Gold",
+            ItemKind::Pickaxe => "Pickaxe",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ItemKind::Potion => "A stoppered flask of something bubbly.",
+            ItemKind::Gold => "A handful of old coins.",
+            ItemKind::Pickaxe => "A sturdy tool for digging through walls.",
+        }
+    }
+
+    pub fn glyph(&self) -> char {
+        match self {
+            ItemKind::Potion => '!',
+            ItemKind::Gold => '$',
+            ItemKind::Pickaxe => '(',
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            ItemKind::Potion => Color::rgb(0.8, 0.3, 1.0),
+            ItemKind::Gold => Color::rgb(1.0, 0.85, 0.0),
+            ItemKind::Pickaxe => Color::rgb(0.7, 0.7, 0.8),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrapKind {
+    /// Drops whoever steps on it into a shallow pit.
+    Pit,
+    /// Shoots a dart at whoever steps on it.
+    Dart,
+    /// Moves whoever steps on it somewhere else on the map.
+    Teleport,
+    /// Tells every monster on the map where the intruder is.
+    Alarm,
+    /// Calls a band of goblins, once.
+    Summon,
+}
+
+impl TrapKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrapKind::Pit => "Pit trap",
+            TrapKind::Dart => "Dart trap",
+            TrapKind::Teleport => "Teleport trap",
+            TrapKind::Alarm => "Alarm trap",
+            TrapKind::Summon => "Summoning trap",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            TrapKind::Pit => Color::rgb(0.6, 0.4, 0.2),
+            TrapKind::Dart => Color::rgb(0.8, 0.8, 0.8),
+            TrapKind::Teleport => Color::rgb(0.8, 0.3, 1.0),
+            TrapKind::Alarm => Color::rgb(1.0, 0.9, 0.2),
+            TrapKind::Summon => Color::rgb(1.0, 0.2, 0.2),
+        }
+    }
+
+    /// Picks a kind, with the nastier ones being rarer.
+    pub fn random(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..10) {
+            0..=2 => TrapKind::Pit,
+            3..=5 => TrapKind::Dart,
+            6 => TrapKind::Teleport,
+            7 | 8 => TrapKind::Alarm,
+            _ => TrapKind::Summon,
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use bevy::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TileType {
+    Wall,
+    Floor,
+    Door { open: bool },
+    Water,
+    DeepWater,
+    Lava,
+    Chasm,
+    Rubble,
+    Grass,
+    Pillar,
+    Statue,
+}
+
+/// Everything the game needs to know about a kind of tile.
+pub struct TileProperties {
+    pub glyph: char,
+    pub fg: Color,
+    pub bg: Color,
+    pub blocks_movement: bool,
+    pub blocks_sight: bool,
+    /// How many times as expensive as plain floor it is to walk onto the tile.
+    pub move_cost: i32,
+    /// Turns of digging it takes to turn the tile into rubble, `None` if it cannot be dug.
+    pub dig_turns: Option<i32>,
+    /// What the tile becomes when a creature that opens doors bumps into it.
+    pub opens_into: Option<TileType>,
+    pub description: &'static str,
+}
+
+impl TileProperties {
+    const DEFAULT: Self = Self {
+        glyph: '.',
+        fg: Color::WHITE,
+        bg: Color::BLACK,
+        blocks_movement: false,
+        blocks_sight: false,
+        move_cost: 1,
+        dig_turns: None,
+        opens_into: None,
+        description: "floor",
+    };
+
+    /// Blocks both movement and sight, like rock.
+    pub fn is_solid(&self) -> bool {
+        self.blocks_movement && self.blocks_sight
+    }
+}
+
+const WALL: TileProperties = TileProperties {
+    glyph: '#',
+    blocks_movement: true,
+    blocks_sight: true,
+    dig_turns: Some(3),
+    description: "wall",
+    ..TileProperties::DEFAULT
+};
+const FLOOR: TileProperties = TileProperties::DEFAULT;
+const CLOSED_DOOR: TileProperties = TileProperties {
+    glyph: '+',
+    fg: Color::rgb(0.7, 0.45, 0.2),
+    blocks_movement: true,
+    blocks_sight: true,
+    opens_into: Some(TileType::Door { open: true }),
+    description: "closed door",
+    ..TileProperties::DEFAULT
+};
+const OPEN_DOOR: TileProperties = TileProperties {
+    glyph: '\'',
+    fg: Color::rgb(0.7, 0.45, 0.2),
+    description: "open door",
+    ..TileProperties::DEFAULT
+};
+const WATER: TileProperties = TileProperties {
+    glyph: '~',
+    fg: Color::rgb(0.4, 0.6, 1.0),
+    bg: Color::rgb(0.0, 0.05, 0.25),
+    move_cost: 2,
+    description: "shallow water",
+    ..TileProperties::DEFAULT
+};
+const DEEP_WATER: TileProperties = TileProperties {
+    glyph: '~',
+    fg: Color::rgb(0.2, 0.3, 0.9),
+    bg: Color::rgb(0.0, 0.0, 0.45),
+    blocks_movement: true,
+    description: "deep water",
+    ..TileProperties::DEFAULT
+};
+const LAVA: TileProperties = TileProperties {
+    glyph: '~',
+    fg: Color::rgb(1.0, 0.6, 0.0),
+    bg: Color::rgb(0.5, 0.05, 0.0),
+    blocks_movement: true,
+    description: "lava",
+    ..TileProperties::DEFAULT
+};
+const CHASM: TileProperties = TileProperties {
+    glyph: ':',
+    fg: Color::rgb(0.3, 0.3, 0.35),
+    blocks_movement: true,
+    description: "chasm",
+    ..TileProperties::DEFAULT
+};
+const RUBBLE: TileProperties = TileProperties {
+    glyph: '%',
+    fg: Color::rgb(0.6, 0.5, 0.4),
+    move_cost: 2,
+    description: "rubble",
+    ..TileProperties::DEFAULT
+};
+const GRASS: TileProperties = TileProperties {
+    glyph: '"',
+    fg: Color::rgb(0.3, 0.8, 0.3),
+    description: "grass",
+    ..TileProperties::DEFAULT
+};
+const PILLAR: TileProperties = TileProperties {
+    glyph: 'O',
+    fg: Color::rgb(0.8, 0.8, 0.8),
+    blocks_movement: true,
+    blocks_sight: true,
+    dig_turns: Some(4),
+    description: "pillar",
+    ..TileProperties::DEFAULT
+};
+const STATUE: TileProperties = TileProperties {
+    glyph: '&',
+    fg: Color::rgb(0.9, 0.9, 0.7),
+    blocks_movement: true,
+    dig_turns: Some(5),
+    description: "statue",
+    ..TileProperties::DEFAULT
+};
+
+impl TileType {
+    pub fn properties(&self) -> &'static TileProperties {
+        match self {
+            TileType::Wall => &WALL,
+            TileType::Floor => &FLOOR,
+            TileType::Door { open: false } => &CLOSED_DOOR,
+            TileType::Door { open: true } => &OPEN_DOOR,
+            TileType::Water => &WATER,
+            TileType::DeepWater => &DEEP_WATER,
+            TileType::Lava => &LAVA,
+            TileType::Chasm => &CHASM,
+            TileType::Rubble => &RUBBLE,
+            TileType::Grass => &GRASS,
+            TileType::Pillar => &PILLAR,
+            TileType::Statue => &STATUE,
+        }
+    }
+
+    pub fn is_door(&self) -> bool {
+        matches!(self, TileType::Door { .. })
+    }
+}
@@ -1,11 +1,11 @@
-use std::ops::Add;
+use std::ops::{Add, Sub};
 
 use bevy::prelude::*;
 use rand::Rng;
 
 use super::{HEIGHT, WIDTH};
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
 pub struct Vec2Int {
     pub x: i32,
     pub y: i32,
@@ -16,16 +16,38 @@ impl Vec2Int {
         Self { x, y }
     }
 
-    pub const ZERO: Self = Self::new(0, 0);
     pub const LEFT: Self = Self::new(-1, 0);
     pub const RIGHT: Self = Self::new(1, 0);
     pub const UP: Self = Self::new(0, 1);
     pub const DOWN: Self = Self::new(0, -1);
+    pub const UP_LEFT: Self = Self::new(-1, 1);
+    pub const UP_RIGHT: Self = Self::new(1, 1);
+    pub const DOWN_LEFT: Self = Self::new(-1, -1);
+    pub const DOWN_RIGHT: Self = Self::new(1, -1);
 
-    pub const DIRECTIONS: [Self; 4] = [Self::LEFT, Self::RIGHT, Self::UP, Self::DOWN];
+    pub const DIRECTIONS: [Self; 8] = [
+        Self::LEFT,
+        Self::RIGHT,
+        Self::UP,
+        Self::DOWN,
+        Self::UP_LEFT,
+        Self::UP_RIGHT,
+        Self::DOWN_LEFT,
+        Self::DOWN_RIGHT,
+    ];
 
-    pub fn to_world(self) -> Vec3 {
-        Vec3::new(self.x as f32 * WIDTH, self.y as f32 * HEIGHT, 1.0)
+    /// Center of the tile in the world, at depth `z`, see
+    /// [`RenderLayer`](super::components::RenderLayer).
+    pub fn to_world(self, z: f32) -> Vec3 {
+        Vec3::new(self.x as f32 * WIDTH, self.y as f32 * HEIGHT, z)
+    }
+
+    /// Grid cell containing a world position, the inverse of [`Vec2Int::to_world`].
+    pub fn from_world(position: Vec2) -> Self {
+        Self::new(
+            (position.x / WIDTH).round() as i32,
+            (position.y / HEIGHT).round() as i32,
+        )
     }
 
     pub fn distance(&self, other: &Self) -> f32 {
@@ -35,6 +57,17 @@ impl Vec2Int {
         (squared as f32).sqrt()
     }
 
+    /// Distance where a straight step costs `straight` and a diagonal step costs `diagonal`.
+    pub fn octile_distance(&self, other: &Self, straight: i32, diagonal: i32) -> i32 {
+        let x = (other.x - self.x).abs();
+        let y = (other.y - self.y).abs();
+        straight * (x.max(y) - x.min(y)) + diagonal * x.min(y)
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        self.x != 0 && self.y != 0
+    }
+
     pub fn random_direction() -> Self {
         let mut rng = rand::thread_rng();
         let index = rng.gen_range(0..Self::DIRECTIONS.len());
@@ -63,3 +96,31 @@ impl Add<&Self> for Vec2Int {
         }
     }
 }
+
+impl Sub for Vec2Int {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+        }
+    }
+}
+
+#[test]
+fn test_distances() {
+    let start = Vec2Int::new(1, 1);
+    let end = Vec2Int::new(4, 2);
+    assert_eq!(start.octile_distance(&end, 10, 14), 34);
+    assert!((end - start + Vec2Int::UP).is_diagonal());
+    assert!(!(end - start - Vec2Int::UP).is_diagonal());
+}
+
+#[test]
+fn test_from_world() {
+    let position = Vec2Int::new(12, -3);
+    assert_eq!(Vec2Int::from_world(position.to_world(0.0).truncate()), position);
+    let offset = Vec2::new(WIDTH * 0.4, -HEIGHT * 0.4);
+    assert_eq!(Vec2Int::from_world(position.to_world(0.0).truncate() + offset), position);
+}
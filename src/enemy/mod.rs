@@ -1,17 +1,15 @@
 
 use bevy::prelude::*;
+use rand::Rng;
 
-use crate::{map_generator::{Map, viewshed::Viewshed}, common::{resources::CharsetAsset, components::Position, Vec2Int, WIDTH, HEIGHT, states::GameState, TileType}, player::Player};
-
-use self::pathfinding::Path;
-
-mod pathfinding;
+use crate::{animation::{AnimationQueue, Lunge}, map_generator::{Map, TileChanged, viewshed::Viewshed}, common::{pathfinding::Path, resources::MovementRules, components::{Description, LightSource, Mobility, Position, RenderLayer, Renderable}, spawns::{Monster, Spawn}, Vec2Int, states::GameState}, player::Player, trap::TrapMemory};
 
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Setup), spawn_enemies)
+        app.init_resource::<MonsterIds>()
+            .add_systems(OnEnter(GameState::Setup), spawn_enemies)
             .add_systems(Update, transition_to_plan_state.run_if(in_state(GameState::EnemyTurn)))
             .add_systems(OnEnter(GameState::PlanEnemyTurn), plan_enemy_actions)
             .add_systems(Update, transition_to_act_state.run_if(in_state(GameState::PlanEnemyTurn)))
@@ -19,52 +17,102 @@ impl Plugin for EnemyPlugin {
                 act_enemy_actions,
                 enemy_wander
             ))
-            .add_systems(Update, transition_to_player_state.run_if(in_state(GameState::ActEnemyTurn)));
+            .add_systems(Update, transition_to_player_state.run_if(in_state(GameState::ActEnemyTurn)))
+            .add_systems(PostUpdate, invalidate_paths);
     }
 }
 
+/// How many of the dark rooms are home to a fire beetle instead of a goblin.
+const FIRE_BEETLE_CHANCE: f64 = 0.5;
+
 #[derive(Component)]
-struct Enemy;
+pub struct Enemy;
+
+/// Numbers the monsters in the order they appear, so no two ever share a name.
+#[derive(Resource, Default)]
+pub struct MonsterIds {
+    next: usize,
+}
+
+impl MonsterIds {
+    pub fn next(&mut self) -> usize {
+        self.next += 1;
+        self.next - 1
+    }
+}
 
 fn spawn_enemies(
     map: Res<Map>,
-    atlas: Res<CharsetAsset>,
+    mut ids: ResMut<MonsterIds>,
     mut commands: Commands,
 ) {
-    for (id, room) in map.rooms.iter().skip(1).enumerate() {
+    for (position, monster) in monster_spawns(&map) {
+        spawn_monster(&mut commands, monster, position, ids.next());
+    }
+}
+
+/// One monster in the middle of every room but the first, and the ones prefabs place.
+fn monster_spawns(map: &Map) -> Vec<(Vec2Int, Monster)> {
+    let mut rng = rand::thread_rng();
+    // Fire beetles like it dark and bring their own light.
+    let in_rooms = map.rooms.iter().skip(1).map(|room| {
         let (x, y) = room.center();
+        let dark = !map.lit[map.xy_idx(x, y)];
+        let monster = if dark && rng.gen_bool(FIRE_BEETLE_CHANCE) {
+            Monster::FireBeetle
+        } else {
+            Monster::Goblin
+        };
+        (Vec2Int::new(x, y), monster)
+    });
+    let from_prefabs = map.spawns.iter().filter_map(|(position, spawn)| match spawn {
+        Spawn::Monster(monster) => Some((*position, *monster)),
+        Spawn::Item(_) | Spawn::Trap(_) | Spawn::Brazier => None,
+    });
+
+    in_rooms.chain(from_prefabs).collect()
+}
 
-        commands
-            .spawn(SpriteSheetBundle {
-                texture_atlas: atlas.atlas.clone(),
-                sprite: TextureAtlasSprite {
-                    custom_size: Some(Vec2::new(1.0, 1.0)),
-                    index: 'g' as usize,
-                    color: Color::rgb(1.0, 0.0, 0.0),
-                    ..Default::default()
-                },
-                transform: Transform::from_scale(Vec3::new(WIDTH, HEIGHT, 1.0)),
-                ..Default::default()
-            })
-            .insert(Name::from(format!("Enemy {}", id)))
-            .insert(Position(Vec2Int::new(x, y)))
-            .insert(Viewshed {range: 8.0})
-            .insert(Enemy);
+pub fn spawn_monster(
+    commands: &mut Commands,
+    monster: Monster,
+    position: Vec2Int,
+    id: usize,
+) {
+    let entity = commands
+        .spawn(Renderable {
+            glyph: monster.glyph(),
+            fg: monster.color(),
+            bg: None,
+            layer: RenderLayer::Creatures,
+        })
+        .insert(Name::from(format!("{} {}", monster.name(), id)))
+        .insert(Description(monster.description()))
+        .insert(Position(position))
+        .insert(Viewshed::new(8.0))
+        .insert(Mobility { opens_doors: monster.opens_doors(), digs: monster.digs() })
+        .insert(TrapMemory::default())
+        .insert(AnimationQueue::default())
+        .insert(Enemy)
+        .id();
+    if let Some(radius) = monster.light_radius() {
+        commands.entity(entity).insert(LightSource { radius });
     }
 }
 
 fn plan_enemy_actions(
     map: Res<Map>,
-    enemies: Query<(&Viewshed, &Position, Entity), With<Enemy>>,
+    rules: Res<MovementRules>,
+    enemies: Query<(&Viewshed, &Position, &Mobility, &TrapMemory, Entity), With<Enemy>>,
     players: Query<&Position, With<Player>>,
     mut commands: Commands,
 ) {
     let Ok(player) = players.get_single() else {
         return;
     };
-    for (viewshed, position, entity) in &enemies {
-        if map.is_visible(position.0, player.0) && position.0.distance(&player.0) < viewshed.range {
-            if let Some(path) = Path::calculate(position.0, player.0, &map) {
+    for (viewshed, position, mobility, memory, entity) in &enemies {
+        if viewshed.visible.contains(&player.0) {
+            if let Some(path) = Path::calculate(position.0, player.0, &map, &rules, *mobility, &memory.0) {
                 commands.entity(entity).insert(path);
             }
         }
@@ -88,11 +136,27 @@ fn transition_to_player_state(
 }
 
 fn act_enemy_actions(
-    mut enemies: Query<(&mut Position, &mut Path, Entity), With<Enemy>>,
+    mut map: ResMut<Map>,
+    rules: Res<MovementRules>,
+    mut enemies: Query<(&mut Position, &mut Path, &Mobility, Entity), With<Enemy>>,
+    mut lunges: EventWriter<Lunge>,
     mut commands: Commands,
 ) {
-    for (mut pos, mut path, entity) in &mut enemies {
-        if let Some(point) = path.waypoints.pop_front() {
+    for (mut pos, mut path, mobility, entity) in &mut enemies {
+        let Some(&point) = path.waypoints.front() else {
+            commands.entity(entity).remove::<Path>();
+            continue;
+        };
+        if mobility.opens_doors && map.open_door(point) {
+            continue;
+        }
+        if mobility.digs && map.is_diggable(point) {
+            map.dig(point);
+            lunges.send(Lunge { entity, toward: point });
+            continue;
+        }
+        path.waypoints.pop_front();
+        if map.can_move(pos.0, point, &rules) {
             pos.0 = point;
         }
         else {
@@ -101,15 +165,47 @@ fn act_enemy_actions(
     }
 }
 
+/// Drops paths that lead over a changed tile, they are planned again next turn.
+fn invalidate_paths(
+    mut changes: EventReader<TileChanged>,
+    enemies: Query<(Entity, &Path), With<Enemy>>,
+    mut commands: Commands,
+) {
+    for change in changes.read() {
+        for (entity, path) in &enemies {
+            if path.waypoints.contains(&change.position) {
+                commands.entity(entity).remove::<Path>();
+            }
+        }
+    }
+}
+
 fn enemy_wander(
-    map: Res<Map>,
-    mut enemies: Query<&mut Position, (With<Enemy>, Without<Path>)>,
+    mut map: ResMut<Map>,
+    rules: Res<MovementRules>,
+    mut enemies: Query<(&mut Position, &Mobility, &TrapMemory), (With<Enemy>, Without<Path>)>,
 ) {
-    for mut enemy in &mut enemies {
+    for (mut enemy, mobility, memory) in &mut enemies {
         let next_direction = enemy.0 + Vec2Int::random_direction();
-        let idx = map.xy_idx(next_direction.x, next_direction.y);
-        if map.tiles[idx] != TileType::Wall {
+        if memory.0.contains(&next_direction) {
+            continue;
+        }
+        if mobility.opens_doors && map.open_door(next_direction) {
+            continue;
+        }
+        if map.can_move(enemy.0, next_direction, &rules) {
             enemy.0 = next_direction;
         }
     }
 }
+
+#[test]
+fn test_monster_spawns() {
+    for _ in 0..20 {
+        let map = Map::generate(&[]).unwrap();
+        for (position, monster) in monster_spawns(&map) {
+            let tile = map.tiles[map.xy_idx(position.x, position.y)];
+            assert!(!tile.properties().blocks_movement, "{:?} starts on {:?}", monster, tile);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+
+use crate::{
+    common::{
+        components::{Description, Mobility, Position, RenderLayer, Renderable},
+        spawns::{ItemKind, Spawn},
+        states::GameState,
+    },
+    map_generator::Map,
+    player::Player,
+};
+
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Setup), spawn_items)
+            .add_systems(Update, pick_up_tools);
+    }
+}
+
+#[derive(Component)]
+pub struct Item(pub ItemKind);
+
+fn spawn_items(map: Res<Map>, mut commands: Commands) {
+    for (position, spawn) in &map.spawns {
+        let Spawn::Item(kind) = spawn else {
+            continue;
+        };
+        commands
+            .spawn(Renderable {
+                glyph: kind.glyph(),
+                fg: kind.color(),
+                bg: None,
+                layer: RenderLayer::Items,
+            })
+            .insert(Name::from(kind.name()))
+            .insert(Description(kind.description()))
+            .insert(Position(*position))
+            .insert(Item(*kind));
+    }
+}
+
+/// There is no inventory yet, so tools are put to use as soon as the player steps on them.
+fn pick_up_tools(
+    mut players: Query<(&Position, &mut Mobility), With<Player>>,
+    items: Query<(Entity, &Item, &Position), Without<Player>>,
+    mut commands: Commands,
+) {
+    let Ok((player, mut mobility)) = players.get_single_mut() else {
+        return;
+    };
+    for (entity, item, position) in &items {
+        if position.0 != player.0 {
+            continue;
+        }
+        if item.0 == ItemKind::Pickaxe {
+            mobility.digs = true;
+            commands.entity(entity).despawn();
+        }
+    }
+}
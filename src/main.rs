@@ -1,19 +1,38 @@
+// Bevy systems routinely take many parameters and nested query filters.
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use animation::AnimationPlugin;
+use camera::{CameraPlugin, CameraView};
 use bevy::{prelude::*, log::LogPlugin};
 
 #[cfg(feature = "debug")]
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
-use common::{resources::CharsetAsset, states::GameState};
+use common::{resources::{CharsetAsset, MovementRules}, states::GameState};
 use enemy::EnemyPlugin;
+use item::ItemPlugin;
 use map_generator::MapGeneratorPlugin;
 use player::PlayerPlugin;
 use system::render;
+#[cfg(not(feature = "terminal"))]
+use system::{spawn_sprites, update_sprites};
+use targeting::TargetingPlugin;
+use trap::TrapPlugin;
+use ui::InterfacePlugin;
 
+mod animation;
+mod camera;
 mod common;
 mod enemy;
+mod item;
 mod map_generator;
 mod player;
 mod system;
+mod targeting;
+#[cfg(feature = "terminal")]
+mod terminal;
+mod trap;
+mod ui;
 
 #[derive(Component)]
 struct MainCamera;
@@ -34,9 +53,7 @@ fn setup(
     });
 
     // Add a 2D Camera
-    let mut cam = Camera2dBundle::default();
-    cam.transform.scale = Vec3::new(0.5, 0.5, 1.0);
-    commands.spawn((MainCamera, cam));
+    commands.spawn((MainCamera, Camera2dBundle::default(), CameraView::default()));
 }
 
 fn switch_to_setup_state(mut state: ResMut<NextState<GameState>>) {
@@ -50,16 +67,29 @@ fn switch_to_normal_play(mut state: ResMut<NextState<GameState>>) {
 fn main() {
     let mut app = App::new();
 
-    app.add_state::<GameState>()
-        .add_plugins(DefaultPlugins.set(
+    app.add_state::<GameState>();
+
+    #[cfg(not(feature = "terminal"))]
+    app.add_plugins(DefaultPlugins.set(
         // This sets image filtering to nearest
         // This is done to prevent textures with low resolution (e.g. pixel art) from being blurred
         // by linear filtering.
         ImagePlugin::default_nearest(),
     ).disable::<LogPlugin>());
 
+    #[cfg(feature = "terminal")]
+    app.add_plugins((
+        // Log output would scribble over the screen.
+        terminal::headless_plugins().disable::<LogPlugin>(),
+        terminal::TerminalPlugin,
+    ))
+    // Nothing on the terminal is drawn from transforms, animations would only hold turns back.
+    .insert_resource(animation::AnimationSettings {
+        enabled: false,
+        ..default()
+    });
 
-    #[cfg(not(feature = "debug"))]
+    #[cfg(not(any(feature = "debug", feature = "terminal")))]
     app.add_plugins(LogPlugin::default());
 
     #[cfg(feature = "debug")]
@@ -72,7 +102,14 @@ fn main() {
             PlayerPlugin,
             MapGeneratorPlugin,
             EnemyPlugin,
+            ItemPlugin,
+            TrapPlugin,
+            AnimationPlugin,
+            CameraPlugin,
+            InterfacePlugin,
+            TargetingPlugin,
         ))
+        .init_resource::<MovementRules>()
         .add_systems(Startup, setup)
         .add_systems(
             Update,
@@ -82,6 +119,10 @@ fn main() {
             Update,
             switch_to_normal_play.run_if(in_state(GameState::Setup)),
         )
-        .add_systems(Update, render)
-        .run();
+        .add_systems(Update, render);
+
+    #[cfg(not(feature = "terminal"))]
+    app.add_systems(Update, (spawn_sprites, update_sprites));
+
+    app.run();
 }
@@ -0,0 +1,242 @@
+use bevy::{prelude::*, utils::HashMap};
+use fixedbitset::FixedBitSet;
+
+use crate::common::{
+    components::{LightSource, Position, RenderLayer, Renderable},
+    spawns::Spawn,
+    Vec2Int,
+};
+
+use super::{viewshed::Viewshed, Map, TileChanged};
+
+/// How brightly every tile is lit, from 0 (dark) to 1 (fully lit).
+#[derive(Resource, Default)]
+pub struct LightMap {
+    levels: Vec<f32>,
+    /// Where every light source shone from the last time it was lit, and how far.
+    shining: HashMap<Entity, (Vec2Int, f32)>,
+}
+
+impl LightMap {
+    /// Lit rooms are fully lit, light sources fade out towards the edge of their radius and do
+    /// not shine through anything that blocks sight.
+    pub fn compute<'a>(
+        map: &Map,
+        sources: impl Iterator<Item = (Vec2Int, &'a LightSource)>,
+    ) -> Self {
+        let mut light = Self {
+            levels: map
+                .lit
+                .iter()
+                .map(|lit| if *lit { 1.0 } else { 0.0 })
+                .collect(),
+            shining: HashMap::new(),
+        };
+        for (from, source) in sources {
+            light.shine(map, from, source.radius, |_| true);
+        }
+        light
+    }
+
+    /// Lights the tiles in `areas` again from scratch, every other tile keeps its level.
+    /// `sources` are all light sources, only those reaching into an area are traced.
+    fn relight(&mut self, map: &Map, sources: &[(Vec2Int, f32)], areas: &[(Vec2Int, f32)]) {
+        let mut stale = FixedBitSet::with_capacity(map.len());
+        for &(center, radius) in areas {
+            for to in around(center, radius).filter(|to| map.in_bounds(*to)) {
+                stale.insert(map.xy_idx(to.x, to.y));
+            }
+        }
+        for idx in stale.ones() {
+            self.levels[idx] = if map.lit[idx] { 1.0 } else { 0.0 };
+        }
+        for &(from, radius) in sources {
+            let reach = radius.ceil() as i32;
+            let overlaps = areas.iter().any(|(center, area)| {
+                let apart = reach + area.ceil() as i32;
+                (from.x - center.x).abs() <= apart && (from.y - center.y).abs() <= apart
+            });
+            if overlaps {
+                self.shine(map, from, radius, |idx| stale.contains(idx));
+            }
+        }
+    }
+
+    /// Adds the light of one source to the tiles for which `within` holds.
+    fn shine(&mut self, map: &Map, from: Vec2Int, radius: f32, within: impl Fn(usize) -> bool) {
+        for to in around(from, radius) {
+            let distance = from.distance(&to);
+            if !map.in_bounds(to) || distance > radius {
+                continue;
+            }
+            let idx = map.xy_idx(to.x, to.y);
+            if !within(idx) || !map.is_visible(from, to) {
+                continue;
+            }
+            self.levels[idx] = self.levels[idx].max(1.0 - distance / (radius + 1.0));
+        }
+    }
+
+    pub fn level(&self, map: &Map, position: Vec2Int) -> f32 {
+        self.levels
+            .get(map.xy_idx(position.x, position.y))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    pub fn is_lit(&self, map: &Map, position: Vec2Int) -> bool {
+        self.level(map, position) > 0.0
+    }
+}
+
+/// Every tile in the square around `center` that a circle of `radius` fits into.
+fn around(center: Vec2Int, radius: f32) -> impl Iterator<Item = Vec2Int> {
+    let reach = radius.ceil() as i32;
+    (-reach..=reach).flat_map(move |y| (-reach..=reach).map(move |x| center + Vec2Int::new(x, y)))
+}
+
+/// Braziers are not in anyone's way, they only light up the room they stand in.
+pub fn spawn_braziers(map: Res<Map>, mut commands: Commands) {
+    for (position, spawn) in &map.spawns {
+        if *spawn != Spawn::Brazier {
+            continue;
+        }
+        commands
+            .spawn(Renderable {
+                glyph: '*',
+                fg: Color::rgb(1.0, 0.6, 0.1),
+                bg: None,
+                layer: RenderLayer::Items,
+            })
+            .insert(Name::from("Brazier"))
+            .insert(Position(*position))
+            .insert(LightSource { radius: 5.0 });
+    }
+}
+
+/// Lights the map again around light sources that moved, came or went and around tiles that
+/// changed. Only viewsheds that reach into the relit area are computed again.
+pub fn update_lighting(
+    map: Res<Map>,
+    mut light: ResMut<LightMap>,
+    mut changes: EventReader<TileChanged>,
+    mut removed: RemovedComponents<LightSource>,
+    sources: Query<(Entity, &Position, &LightSource)>,
+    moved: Query<(Entity, &Position, &LightSource), Or<(Changed<Position>, Changed<LightSource>)>>,
+    mut viewers: Query<(&Position, &mut Viewshed)>,
+) {
+    if light.levels.len() != map.len() {
+        *light = LightMap::compute(
+            &map,
+            sources
+                .iter()
+                .map(|(_, position, source)| (position.0, source)),
+        );
+        light.shining = sources
+            .iter()
+            .map(|(entity, position, source)| (entity, (position.0, source.radius)))
+            .collect();
+        for (_, mut viewshed) in &mut viewers {
+            viewshed.dirty = true;
+        }
+        return;
+    }
+
+    // Keeping track of the sources alone does not change how the map is lit.
+    let shining = &mut light.bypass_change_detection().shining;
+    let mut areas: Vec<(Vec2Int, f32)> = removed
+        .read()
+        .filter_map(|entity| shining.remove(&entity))
+        .collect();
+    for (entity, position, source) in &moved {
+        let area = (position.0, source.radius);
+        match shining.insert(entity, area) {
+            // Opening doors and digging touch the position without moving.
+            Some(old) if old == area => continue,
+            Some(old) => areas.push(old),
+            None => {}
+        }
+        areas.push(area);
+    }
+    // A tile that starts or stops blocking sight changes what the sources around it reach.
+    for change in changes.read() {
+        areas.extend(
+            shining
+                .values()
+                .filter(|(from, radius)| from.distance(&change.position) <= *radius),
+        );
+    }
+    if areas.is_empty() {
+        return;
+    }
+
+    let shining: Vec<(Vec2Int, f32)> = shining.values().copied().collect();
+    light.relight(&map, &shining, &areas);
+    for (position, mut viewshed) in &mut viewers {
+        let reaches = areas
+            .iter()
+            .any(|(center, radius)| position.0.distance(center) <= viewshed.range + radius);
+        if reaches {
+            viewshed.dirty = true;
+        }
+    }
+}
+
+#[test]
+fn test_light_map() {
+    let (mut map, markers) = Map::from_ascii(
+        "
+        ##########
+        #s...#..d#
+        #....#...#
+        ##########",
+    )
+    .unwrap();
+    let [source, dark] = ['s', 'd'].map(|marker| markers[&marker][0]);
+    let torch = LightSource { radius: 6.0 };
+    let light = LightMap::compute(&map, [(source, &torch)].into_iter());
+    assert_eq!(light.level(&map, source), 1.0);
+    let near = light.level(&map, source + Vec2Int::RIGHT);
+    assert!(light.level(&map, source + Vec2Int::new(2, 0)) < near);
+    // The wall facing the light is lit, the room behind it is not.
+    assert!(light.is_lit(&map, Vec2Int::new(5, 1)));
+    assert!(!light.is_lit(&map, Vec2Int::new(6, 1)));
+    assert!(!light.is_lit(&map, dark));
+
+    let idx = map.xy_idx(dark.x, dark.y);
+    map.lit[idx] = true;
+    let light = LightMap::compute(&map, std::iter::empty());
+    assert_eq!(light.level(&map, dark), 1.0);
+    assert!(!light.is_lit(&map, source));
+}
+
+#[test]
+fn test_relight() {
+    let (map, markers) = Map::from_ascii(
+        "
+        ############
+        #a...#....f#
+        #..b.....c.#
+        ############",
+    )
+    .unwrap();
+    let [a, b, c, far] = ['a', 'b', 'c', 'f'].map(|marker| markers[&marker][0]);
+    let torch = LightSource { radius: 4.0 };
+    let brazier = LightSource { radius: 2.0 };
+
+    // The torch is carried from a to b while the brazier keeps burning.
+    let mut light = LightMap::compute(&map, [(a, &torch), (far, &brazier)].into_iter());
+    light.relight(
+        &map,
+        &[(b, torch.radius), (far, brazier.radius)],
+        &[(a, torch.radius), (b, torch.radius)],
+    );
+    let expected = LightMap::compute(&map, [(b, &torch), (far, &brazier)].into_iter());
+    assert_eq!(light.levels, expected.levels);
+
+    // Taking the brazier away only darkens its own corner.
+    light.relight(&map, &[(b, torch.radius)], &[(far, brazier.radius)]);
+    let expected = LightMap::compute(&map, [(b, &torch)].into_iter());
+    assert_eq!(light.levels, expected.levels);
+    assert!(!light.is_lit(&map, c + Vec2Int::RIGHT));
+}
@@ -1,24 +1,100 @@
-use std::cmp::{max, min};
+use std::{
+    cmp::{max, min},
+    collections::HashMap,
+    fmt,
+};
 
 use bevy::prelude::*;
 use rand::Rng;
 use bresenham::*;
 
-use crate::common::{rect::Rect, TileType, Vec2Int};
+use crate::common::{
+    components::Mobility,
+    rect::Rect,
+    resources::MovementRules,
+    spawns::{Spawn, TrapKind},
+    TileType, Vec2Int,
+};
+
+use super::prefab::{Cell, Placement, Prefab};
+
+/// Fewer rooms than this make for a level that is not worth playing.
+const MIN_ROOMS: usize = 2;
+/// How many fresh maps are generated before giving up on one that cannot be repaired.
+const MAX_ATTEMPTS: usize = 10;
+/// Validation assumes the player, who can open every door.
+const PLAYER: Mobility = Mobility {
+    opens_doors: true,
+    digs: false,
+};
+/// How many of the corridor openings of a room get a door.
+const DOOR_CHANCE: f64 = 0.6;
+/// How many of the rooms other than the first hide a trap.
+const ROOM_TRAP_CHANCE: f64 = 0.25;
+/// How many of the rooms other than the first are lit, and how many of the dark ones get a
+/// brazier instead.
+const LIT_ROOM_CHANCE: f64 = 0.5;
+const BRAZIER_CHANCE: f64 = 0.4;
+
+/// Sent for every tile that changed while the game is running.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct TileChanged {
+    pub position: Vec2Int,
+    pub old: TileType,
+    pub new: TileType,
+}
 
 #[derive(Resource)]
 pub struct Map {
+    /// Written directly while generating. Once the game runs, use [`Map::set_tile`] so the
+    /// rest of the game hears about the change.
     pub tiles: Vec<TileType>,
     pub rooms: Vec<Rect>,
+    /// Monsters, items, traps and braziers placed on the map while generating it.
+    pub spawns: Vec<(Vec2Int, Spawn)>,
+    /// Tiles that are lit without any light source, the lit rooms and their walls.
+    pub lit: Vec<bool>,
     pub width: i32,
     pub height: i32,
+    /// Changes made by [`Map::set_tile`] that were not sent as events yet.
+    changes: Vec<TileChanged>,
+    /// Turns already spent digging at tiles that are not dug out yet.
+    dig_progress: HashMap<Vec2Int, i32>,
 }
 
 impl Map {
-    pub fn new() -> Self {
+    /// Generates a map, stamps prefabs onto it and repairs or rerolls it until it passes
+    /// [`Map::validate`].
+    pub fn generate(prefabs: &[Prefab]) -> Result<Self, MapGenError> {
+        let mut result = Err(MapGenError::TooFewRooms {
+            found: 0,
+            required: MIN_ROOMS,
+        });
+        for attempt in 1..=MAX_ATTEMPTS {
+            let mut map = Map::new();
+            map.apply_prefabs(prefabs);
+            map.decorate_rooms();
+            map.repair();
+            map.place_doors();
+            map.place_traps();
+            map.light_rooms();
+            result = map.validate().map(|_| map);
+            match &result {
+                Ok(_) => break,
+                Err(error) => warn!("Discarding generated map {}: {}", attempt, error),
+            }
+        }
+        result
+    }
+
+    fn new() -> Self {
         let mut map = Map {
             tiles: vec![TileType::Wall; 80 * 50],
             rooms: Vec::new(),
+            spawns: Vec::new(),
+            lit: vec![false; 80 * 50],
+            changes: Vec::new(),
+            dig_progress: HashMap::new(),
             width: 80,
             height: 50,
         };
@@ -62,6 +138,471 @@ impl Map {
         map
     }
 
+    /// Builds a map from rows of `#` (wall), `.` (floor), `+` (closed door) and `'` (open door).
+    /// The first row is the top of the map. Any other character is a floor tile whose position
+    /// is returned as a marker. Leading and trailing whitespace and blank lines are ignored, so
+    /// fixtures can be indented.
+    #[cfg(test)]
+    pub fn from_ascii(ascii: &str) -> Result<(Self, HashMap<char, Vec<Vec2Int>>), ParseMapError> {
+        let rows: Vec<&str> = ascii
+            .lines()
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect();
+        let width = rows.first().ok_or(ParseMapError::Empty)?.chars().count();
+        let height = rows.len();
+
+        let mut map = Map {
+            tiles: vec![TileType::Wall; width * height],
+            rooms: Vec::new(),
+            spawns: Vec::new(),
+            lit: vec![false; width * height],
+            changes: Vec::new(),
+            dig_progress: HashMap::new(),
+            width: width as i32,
+            height: height as i32,
+        };
+        let mut markers: HashMap<char, Vec<Vec2Int>> = HashMap::new();
+        for (row, line) in rows.iter().enumerate() {
+            if line.chars().count() != width {
+                return Err(ParseMapError::RaggedRow {
+                    row,
+                    expected: width,
+                    found: line.chars().count(),
+                });
+            }
+            let y = (height - 1 - row) as i32;
+            for (x, c) in line.chars().enumerate() {
+                let idx = map.xy_idx(x as i32, y);
+                map.tiles[idx] = match c {
+                    '#' => TileType::Wall,
+                    '.' => TileType::Floor,
+                    '+' => TileType::Door { open: false },
+                    '\'' => TileType::Door { open: true },
+                    marker => {
+                        markers.entry(marker).or_default().push(Vec2Int::new(x as i32, y));
+                        TileType::Floor
+                    }
+                };
+            }
+        }
+        Ok((map, markers))
+    }
+
+    /// Rolls for every prefab and stamps the ones that come up where they fit.
+    pub fn apply_prefabs(&mut self, prefabs: &[Prefab]) {
+        let mut rng = rand::thread_rng();
+        for prefab in prefabs {
+            if !rng.gen_bool(prefab.chance) {
+                continue;
+            }
+            let prefab = prefab.randomly_oriented(&mut rng);
+            let placed = match prefab.placement {
+                Placement::Room => self.place_in_room(&prefab, &mut rng),
+                Placement::Open => self.place_in_rock(&prefab, &mut rng),
+            };
+            if placed {
+                debug!("Placed prefab {}", prefab.name);
+            } else {
+                debug!("Found no space for prefab {}", prefab.name);
+            }
+        }
+    }
+
+    /// Gives some rooms other than the first a feature: a pool, grass, rubble or pillars. Pools
+    /// are shallow, monsters start in the middle of these rooms and have to get out.
+    fn decorate_rooms(&mut self) {
+        let mut rng = rand::thread_rng();
+        let rooms: Vec<(Rect, Vec2Int)> = self
+            .rooms
+            .iter()
+            .skip(1)
+            .map(|room| {
+                let (x, y) = room.center();
+                (
+                    Rect::new(room.x, room.y, room.w, room.h),
+                    Vec2Int::new(x, y),
+                )
+            })
+            .collect();
+        for (room, center) in rooms {
+            let feature = rng.gen_range(0..6);
+            for y in room.y + 1..=room.y2 {
+                for x in room.x + 1..=room.x2 {
+                    let position = Vec2Int::new(x, y);
+                    let inset = x == room.x + 2 || x == room.x2 - 1;
+                    let tile = match feature {
+                        0 if position.distance(&center) < 2.5 => TileType::Water,
+                        1 if rng.gen_bool(0.5) => TileType::Grass,
+                        2 if rng.gen_bool(0.15) => TileType::Rubble,
+                        3 if inset && (y == room.y + 2 || y == room.y2 - 1) => TileType::Pillar,
+                        _ => continue,
+                    };
+                    let idx = self.xy_idx(x, y);
+                    self.tiles[idx] = tile;
+                }
+            }
+        }
+    }
+
+    /// Replaces a random room that is large enough. The first room is left alone, the player
+    /// starts there.
+    fn place_in_room(&mut self, prefab: &Prefab, rng: &mut impl Rng) -> bool {
+        let candidates: Vec<usize> = self
+            .rooms
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, room)| room.w >= prefab.width() && room.h >= prefab.height())
+            .map(|(index, _)| index)
+            .collect();
+        if candidates.is_empty() {
+            return false;
+        }
+        let room = self.rooms.remove(candidates[rng.gen_range(0..candidates.len())]);
+        let x = room.x + 1 + (room.w - prefab.width()) / 2;
+        let y = room.y + 1 + (room.h - prefab.height()) / 2;
+        self.stamp(prefab, Vec2Int::new(x, y));
+        true
+    }
+
+    /// Looks for solid rock with a one tile margin around the prefab, stamps it there and
+    /// tunnels from its closest opening to the closest room.
+    fn place_in_rock(&mut self, prefab: &Prefab, rng: &mut impl Rng) -> bool {
+        const ATTEMPTS: i32 = 50;
+        let (max_x, max_y) = (self.width - 2 - prefab.width(), self.height - 2 - prefab.height());
+        if max_x < 2 || max_y < 2 {
+            return false;
+        }
+        for _ in 0..ATTEMPTS {
+            let origin = Vec2Int::new(rng.gen_range(2..=max_x), rng.gen_range(2..=max_y));
+            let solid = (origin.y - 1..=origin.y + prefab.height()).all(|y| {
+                (origin.x - 1..=origin.x + prefab.width())
+                    .all(|x| self.tiles[self.xy_idx(x, y)].properties().is_solid())
+            });
+            if solid {
+                self.stamp(prefab, origin);
+                self.connect_prefab(prefab, origin);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Copies the prefab onto the map with its bottom left corner at `origin`.
+    fn stamp(&mut self, prefab: &Prefab, origin: Vec2Int) {
+        for row in 0..prefab.height() {
+            for column in 0..prefab.width() {
+                let position = origin + Vec2Int::new(column, prefab.height() - 1 - row);
+                let idx = self.xy_idx(position.x, position.y);
+                match prefab.cell(column, row) {
+                    Cell::Keep => {}
+                    Cell::Tile(tile) => self.tiles[idx] = tile,
+                    Cell::Spawn(spawn) => {
+                        self.tiles[idx] = TileType::Floor;
+                        self.spawns.push((position, spawn));
+                    }
+                }
+            }
+        }
+    }
+
+    fn connect_prefab(&mut self, prefab: &Prefab, origin: Vec2Int) {
+        let mut openings = Vec::new();
+        for row in 0..prefab.height() {
+            for column in 0..prefab.width() {
+                let walkable = matches!(
+                    prefab.cell(column, row),
+                    Cell::Tile(TileType::Floor) | Cell::Spawn(_)
+                );
+                let outwards = if row == 0 {
+                    Vec2Int::UP
+                } else if row == prefab.height() - 1 {
+                    Vec2Int::DOWN
+                } else if column == 0 {
+                    Vec2Int::LEFT
+                } else if column == prefab.width() - 1 {
+                    Vec2Int::RIGHT
+                } else {
+                    continue;
+                };
+                if walkable {
+                    let position = origin + Vec2Int::new(column, prefab.height() - 1 - row);
+                    openings.push((position + outwards, outwards));
+                }
+            }
+        }
+
+        let closest = openings
+            .iter()
+            .flat_map(|&(outside, outwards)| {
+                self.rooms.iter().map(move |room| {
+                    let (x, y) = room.center();
+                    (outside, outwards, Vec2Int::new(x, y))
+                })
+            })
+            .min_by(|a, b| a.0.distance(&a.2).total_cmp(&b.0.distance(&b.2)));
+        let Some((outside, outwards, target)) = closest else {
+            return;
+        };
+
+        // Leave in the direction of the opening first if the room lies that way, so the tunnel
+        // does not cut back through the prefab.
+        let vertical_first = if outwards.x == 0 {
+            (target.y - outside.y).signum() == outwards.y
+        } else {
+            (target.x - outside.x).signum() != outwards.x
+        };
+        if vertical_first {
+            self.apply_vertical_tunnel(outside.y, target.y, outside.x);
+            self.apply_horizontal_tunnel(outside.x, target.x, target.y);
+        } else {
+            self.apply_horizontal_tunnel(outside.x, target.x, outside.y);
+            self.apply_vertical_tunnel(outside.y, target.y, target.x);
+        }
+    }
+
+    /// Where the player starts: the center of the first room.
+    pub fn start(&self) -> Option<Vec2Int> {
+        let (x, y) = self.rooms.first()?.center();
+        Some(Vec2Int::new(x, y))
+    }
+
+    /// Checks that the map is playable: enough rooms, a solid border and every floor tile
+    /// reachable from the start.
+    pub fn validate(&self) -> Result<(), MapGenError> {
+        let start = self.start().filter(|_| self.rooms.len() >= MIN_ROOMS);
+        let Some(start) = start else {
+            return Err(MapGenError::TooFewRooms {
+                found: self.rooms.len(),
+                required: MIN_ROOMS,
+            });
+        };
+        if let Some(position) = self
+            .border()
+            .find(|border| self.is_passable(self.xy_idx(border.x, border.y), PLAYER))
+        {
+            return Err(MapGenError::FloorOnBorder(position));
+        }
+        let reachable = self.reachable_from(start);
+        if let Some(idx) =
+            (0..self.len()).find(|&idx| self.is_passable(idx, PLAYER) && !reachable[idx])
+        {
+            let (x, y) = self.idx_xy(idx);
+            return Err(MapGenError::Unreachable(Vec2Int::new(x, y)));
+        }
+        Ok(())
+    }
+
+    /// Fixes what [`Map::validate`] would complain about where possible: walls up the border
+    /// and tunnels every cut off area to the closest reachable floor.
+    fn repair(&mut self) {
+        for position in self.border().collect::<Vec<_>>() {
+            let idx = self.xy_idx(position.x, position.y);
+            self.tiles[idx] = TileType::Wall;
+        }
+        let Some(start) = self.start() else {
+            return;
+        };
+        loop {
+            let reachable = self.reachable_from(start);
+            let Some(cut_off) =
+                (0..self.len()).find(|&idx| self.is_passable(idx, PLAYER) && !reachable[idx])
+            else {
+                return;
+            };
+            let (x, y) = self.idx_xy(cut_off);
+            let from = Vec2Int::new(x, y);
+            let closest = (0..self.len())
+                .filter(|&idx| reachable[idx])
+                .map(|idx| {
+                    let (x, y) = self.idx_xy(idx);
+                    Vec2Int::new(x, y)
+                })
+                .min_by(|a, b| from.distance(a).total_cmp(&from.distance(b)))
+                .unwrap_or(start);
+            self.apply_horizontal_tunnel(from.x, closest.x, from.y);
+            self.apply_vertical_tunnel(from.y, closest.y, closest.x);
+        }
+    }
+
+    fn border(&self) -> impl Iterator<Item = Vec2Int> + '_ {
+        let horizontal =
+            (0..self.width).flat_map(|x| [Vec2Int::new(x, 0), Vec2Int::new(x, self.height - 1)]);
+        let vertical =
+            (0..self.height).flat_map(|y| [Vec2Int::new(0, y), Vec2Int::new(self.width - 1, y)]);
+        horizontal.chain(vertical)
+    }
+
+    /// Flood fills the floor from `start` with straight steps only, so the result holds no
+    /// matter how diagonal moves are restricted.
+    fn reachable_from(&self, start: Vec2Int) -> Vec<bool> {
+        let mut reachable = vec![false; self.len()];
+        let mut open = vec![start];
+        reachable[self.xy_idx(start.x, start.y)] = true;
+        while let Some(position) = open.pop() {
+            for direction in [Vec2Int::LEFT, Vec2Int::RIGHT, Vec2Int::UP, Vec2Int::DOWN] {
+                let next = position + direction;
+                if !self.in_bounds(next) {
+                    continue;
+                }
+                let idx = self.xy_idx(next.x, next.y);
+                if !reachable[idx] && self.is_passable(idx, PLAYER) {
+                    reachable[idx] = true;
+                    open.push(next);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Puts doors into the walls of rooms where a single corridor leads out of them.
+    fn place_doors(&mut self) {
+        let mut rng = rand::thread_rng();
+        let rooms: Vec<(i32, i32, i32, i32)> = self
+            .rooms
+            .iter()
+            .map(|room| (room.x, room.y, room.x2 + 1, room.y2 + 1))
+            .collect();
+        for (left, bottom, right, top) in rooms {
+            let horizontal = (left + 1..right).flat_map(|x| {
+                [
+                    (Vec2Int::new(x, bottom), Vec2Int::DOWN),
+                    (Vec2Int::new(x, top), Vec2Int::UP),
+                ]
+            });
+            let vertical = (bottom + 1..top).flat_map(|y| {
+                [
+                    (Vec2Int::new(left, y), Vec2Int::LEFT),
+                    (Vec2Int::new(right, y), Vec2Int::RIGHT),
+                ]
+            });
+            for (position, outwards) in horizontal.chain(vertical).collect::<Vec<_>>() {
+                if self.is_corridor_opening(position, outwards) && rng.gen_bool(DOOR_CHANCE) {
+                    let idx = self.xy_idx(position.x, position.y);
+                    self.tiles[idx] = TileType::Door {
+                        open: rng.gen_bool(0.3),
+                    };
+                }
+            }
+        }
+    }
+
+    /// Hides traps in narrow corridors and in some of the rooms. The first room stays safe, and
+    /// nothing is put where a prefab already placed something.
+    fn place_traps(&mut self) {
+        let mut rng = rand::thread_rng();
+        // Openings in room walls are left for doors.
+        let in_room = |position: Vec2Int| {
+            self.rooms.iter().any(|room| {
+                position.x >= room.x
+                    && position.x <= room.x2 + 1
+                    && position.y >= room.y
+                    && position.y <= room.y2 + 1
+            })
+        };
+        let mut corridors: Vec<Vec2Int> = (0..self.len())
+            .map(|idx| {
+                let (x, y) = self.idx_xy(idx);
+                Vec2Int::new(x, y)
+            })
+            .filter(|&position| {
+                self.is_corridor_opening(position, Vec2Int::UP)
+                    || self.is_corridor_opening(position, Vec2Int::RIGHT)
+            })
+            .filter(|&position| !in_room(position))
+            .collect();
+        for _ in 0..self.rooms.len() / 2 {
+            if corridors.is_empty() {
+                break;
+            }
+            let position = corridors.swap_remove(rng.gen_range(0..corridors.len()));
+            if !self.has_spawn(position) {
+                self.spawns
+                    .push((position, Spawn::Trap(TrapKind::random(&mut rng))));
+            }
+        }
+
+        for index in 1..self.rooms.len() {
+            if !rng.gen_bool(ROOM_TRAP_CHANCE) {
+                continue;
+            }
+            let room = &self.rooms[index];
+            let position = Vec2Int::new(
+                rng.gen_range(room.x + 1..=room.x2),
+                rng.gen_range(room.y + 1..=room.y2),
+            );
+            let (x, y) = room.center();
+            let tile = self.tiles[self.xy_idx(position.x, position.y)];
+            if !tile.properties().blocks_movement
+                && position != Vec2Int::new(x, y)
+                && !self.has_spawn(position)
+            {
+                self.spawns
+                    .push((position, Spawn::Trap(TrapKind::random(&mut rng))));
+            }
+        }
+    }
+
+    /// Lights the first room and some of the others, corridors stay dark. Some of the dark rooms
+    /// get a brazier in one of their corners.
+    fn light_rooms(&mut self) {
+        let mut rng = rand::thread_rng();
+        for index in 0..self.rooms.len() {
+            let room = &self.rooms[index];
+            if index == 0 || rng.gen_bool(LIT_ROOM_CHANCE) {
+                for y in room.y..=room.y2 + 1 {
+                    for x in room.x..=room.x2 + 1 {
+                        let idx = self.xy_idx(x, y);
+                        self.lit[idx] = true;
+                    }
+                }
+                continue;
+            }
+            if !rng.gen_bool(BRAZIER_CHANCE) {
+                continue;
+            }
+            let corner = Vec2Int::new(
+                if rng.gen_bool(0.5) {
+                    room.x + 1
+                } else {
+                    room.x2
+                },
+                if rng.gen_bool(0.5) {
+                    room.y + 1
+                } else {
+                    room.y2
+                },
+            );
+            let tile = self.tiles[self.xy_idx(corner.x, corner.y)];
+            if !tile.properties().blocks_movement && !self.has_spawn(corner) {
+                self.spawns.push((corner, Spawn::Brazier));
+            }
+        }
+    }
+
+    fn has_spawn(&self, position: Vec2Int) -> bool {
+        self.spawns.iter().any(|(other, _)| *other == position)
+    }
+
+    /// Whether `position` in a room wall is a one tile wide gap with floor on both sides.
+    fn is_corridor_opening(&self, position: Vec2Int, outwards: Vec2Int) -> bool {
+        let along = Vec2Int::new(outwards.y, outwards.x);
+        let floor = |position: Vec2Int| {
+            self.in_bounds(position)
+                && !self.tiles[self.xy_idx(position.x, position.y)].properties().blocks_movement
+        };
+        let wall = |position: Vec2Int| {
+            self.in_bounds(position)
+                && self.tiles[self.xy_idx(position.x, position.y)].properties().is_solid()
+        };
+        floor(position)
+            && floor(position + outwards)
+            && floor(position - outwards)
+            && wall(position + along)
+            && wall(position - along)
+    }
+
     fn apply_room_to_map(&mut self, room: &Rect) {
         for y in room.y + 1..=room.y2 {
             for x in room.x + 1..=room.x2 {
@@ -89,29 +630,125 @@ impl Map {
         }
     }
 
-    fn generate_empty_tiles(&self) -> Vec<TileType> {
-        let mut tiles = vec![TileType::Floor; 80 * 50];
+    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
+        (y * self.width) as usize + x as usize
+    }
+
+    pub fn is_occupied(&self, idx: usize) -> bool {
+        self.tiles[idx].properties().blocks_movement
+    }
+
+    /// Whether a creature with `mobility` can get onto the tile, possibly after opening it.
+    pub fn is_passable(&self, idx: usize, mobility: Mobility) -> bool {
+        let properties = self.tiles[idx].properties();
+        if !properties.blocks_movement {
+            return true;
+        }
+        if properties.opens_into.is_some() {
+            return mobility.opens_doors;
+        }
+        let (x, y) = self.idx_xy(idx);
+        mobility.digs && self.is_diggable(Vec2Int::new(x, y))
+    }
+
+    /// Whether the tile can be dug out. The outer border never can, it keeps everyone on the map.
+    pub fn is_diggable(&self, position: Vec2Int) -> bool {
+        let inside = position.x > 0
+            && position.y > 0
+            && position.x < self.width - 1
+            && position.y < self.height - 1;
+        inside
+            && self.tiles[self.xy_idx(position.x, position.y)]
+                .properties()
+                .dig_turns
+                .is_some()
+    }
 
-        for x in 0..self.width {
-            tiles[self.xy_idx(x, 0)] = TileType::Wall;
-            tiles[self.xy_idx(x, self.height - 1)] = TileType::Wall;
+    /// Spends a turn digging at `position`. Once enough turns went in, the tile becomes rubble.
+    pub fn dig(&mut self, position: Vec2Int) {
+        if !self.is_diggable(position) {
+            return;
         }
-        for y in 0..self.height {
-            tiles[self.xy_idx(0, y)] = TileType::Wall;
-            tiles[self.xy_idx(self.width - 1, y)] = TileType::Wall;
+        let tile = self.tiles[self.xy_idx(position.x, position.y)];
+        let turns = tile.properties().dig_turns.unwrap_or(0);
+        let progress = self.dig_progress.entry(position).or_default();
+        *progress += 1;
+        if *progress >= turns {
+            self.set_tile(position, TileType::Rubble);
         }
-        tiles
     }
 
-    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
-        (y * self.width) as usize + x as usize
+    pub fn blocks_sight(&self, idx: usize) -> bool {
+        self.tiles[idx].properties().blocks_sight
     }
 
-    pub fn is_occupied(&self, idx: usize) -> bool {
-        if self.tiles[idx] == TileType::Wall {
-            return true;
+    /// Opens the closed door at `position`. Returns `false` if there is none.
+    pub fn open_door(&mut self, position: Vec2Int) -> bool {
+        if !self.in_bounds(position) {
+            return false;
         }
-        false
+        let idx = self.xy_idx(position.x, position.y);
+        let Some(open) = self.tiles[idx].properties().opens_into else {
+            return false;
+        };
+        self.set_tile(position, open);
+        true
+    }
+
+    /// Replaces the tile at `position` and records a [`TileChanged`] for it.
+    pub fn set_tile(&mut self, position: Vec2Int, tile: TileType) {
+        let idx = self.xy_idx(position.x, position.y);
+        let old = self.tiles[idx];
+        if old == tile {
+            return;
+        }
+        self.tiles[idx] = tile;
+        self.dig_progress.remove(&position);
+        self.changes.push(TileChanged {
+            position,
+            old,
+            new: tile,
+        });
+    }
+
+    pub fn has_changes(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// Hands out the recorded changes, oldest first, and forgets them.
+    pub fn take_changes(&mut self) -> Vec<TileChanged> {
+        std::mem::take(&mut self.changes)
+    }
+
+    pub fn in_bounds(&self, position: Vec2Int) -> bool {
+        position.x >= 0 && position.x < self.width && position.y >= 0 && position.y < self.height
+    }
+
+    /// Checks whether a single step from `from` to the neighbouring `to` is allowed.
+    pub fn can_move(&self, from: Vec2Int, to: Vec2Int, rules: &MovementRules) -> bool {
+        self.can_pass(from, to, rules, Mobility::default())
+    }
+
+    /// Like [`Map::can_move`], but closed doors count as open for creatures that can open them.
+    /// Used for planning paths, where a door only costs the turn it takes to open it.
+    pub fn can_pass(
+        &self,
+        from: Vec2Int,
+        to: Vec2Int,
+        rules: &MovementRules,
+        mobility: Mobility,
+    ) -> bool {
+        if !self.in_bounds(to) || !self.is_passable(self.xy_idx(to.x, to.y), mobility) {
+            return false;
+        }
+        if rules.block_diagonal_squeeze && (to - from).is_diagonal() {
+            let horizontal = self.xy_idx(to.x, from.y);
+            let vertical = self.xy_idx(from.x, to.y);
+            if self.is_occupied(horizontal) && self.is_occupied(vertical) {
+                return false;
+            }
+        }
+        true
     }
 
     pub fn idx_xy(&self, idx: usize) -> (i32, i32) {
@@ -130,10 +767,229 @@ impl Map {
 
         for (x, y) in line {
             let idx = self.xy_idx(x as i32, y as i32);
-            if self.tiles[idx] == TileType::Wall {
+            if self.blocks_sight(idx) {
                 return false;
             }
         }
         true
     }
 }
+
+/// Prints the map in the same layout [`Map::from_ascii`] reads, top row first.
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                write!(f, "{}", self.tiles[self.xy_idx(x, y)].properties().glyph)?;
+            }
+            if y > 0 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MapGenError {
+    TooFewRooms { found: usize, required: usize },
+    FloorOnBorder(Vec2Int),
+    Unreachable(Vec2Int),
+}
+
+impl fmt::Display for MapGenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapGenError::TooFewRooms { found, required } => {
+                write!(f, "only {} rooms, at least {} are needed", found, required)
+            }
+            MapGenError::FloorOnBorder(position) => {
+                write!(f, "floor on the border at ({}, {})", position.x, position.y)
+            }
+            MapGenError::Unreachable(position) => write!(
+                f,
+                "floor at ({}, {}) cannot be reached from the start",
+                position.x, position.y
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+pub enum ParseMapError {
+    Empty,
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+#[test]
+fn test_ascii_round_trip() {
+    let ascii = "\
+        #######
+        #..#..#
+        #.....#
+        #######";
+    let (map, markers) = Map::from_ascii(ascii).unwrap();
+    assert_eq!((map.width, map.height), (7, 4));
+    assert!(markers.is_empty());
+    assert!(map.tiles[map.xy_idx(3, 2)] == TileType::Wall);
+    assert!(map.tiles[map.xy_idx(3, 1)] == TileType::Floor);
+    let expected: Vec<&str> = ascii.lines().map(str::trim).collect();
+    assert_eq!(map.to_string(), expected.join("\n"));
+
+    assert_eq!(
+        Map::from_ascii("###\n##").err(),
+        Some(ParseMapError::RaggedRow {
+            row: 1,
+            expected: 3,
+            found: 2
+        })
+    );
+    assert_eq!(Map::from_ascii("  \n").err(), Some(ParseMapError::Empty));
+}
+
+#[test]
+fn test_visibility() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #######
+        #a..#c#
+        #b...d#
+        #######",
+    )
+    .unwrap();
+    let [a, b, c, d] = ['a', 'b', 'c', 'd'].map(|marker| markers[&marker][0]);
+    assert!(map.is_visible(a, b));
+    assert!(map.is_visible(b, d));
+    assert!(!map.is_visible(a, c));
+}
+
+#[test]
+fn test_validate_and_repair() {
+    let (mut map, _) = Map::from_ascii(
+        "
+        ##########
+        #...##...#
+        #...##...#
+        ##########",
+    )
+    .unwrap();
+    assert_eq!(
+        map.validate(),
+        Err(MapGenError::TooFewRooms {
+            found: 0,
+            required: MIN_ROOMS
+        })
+    );
+
+    map.rooms = vec![Rect::new(0, 0, 3, 2), Rect::new(5, 0, 3, 2)];
+    assert_eq!(
+        map.validate(),
+        Err(MapGenError::Unreachable(Vec2Int::new(6, 1)))
+    );
+    map.repair();
+    assert_eq!(map.validate(), Ok(()));
+
+    let idx = map.xy_idx(0, 1);
+    map.tiles[idx] = TileType::Floor;
+    assert_eq!(
+        map.validate(),
+        Err(MapGenError::FloorOnBorder(Vec2Int::new(0, 1)))
+    );
+    map.repair();
+    assert_eq!(map.validate(), Ok(()));
+}
+
+#[test]
+fn test_doors() {
+    let (mut map, markers) = Map::from_ascii(
+        "
+        #######
+        #a.+.b#
+        #######",
+    )
+    .unwrap();
+    let [a, b] = ['a', 'b'].map(|marker| markers[&marker][0]);
+    let door = Vec2Int::new(3, 1);
+    let rules = MovementRules::default();
+    assert!(!map.is_visible(a, b));
+    assert!(!map.can_move(door + Vec2Int::LEFT, door, &rules));
+    assert!(map.open_door(door));
+    assert!(!map.open_door(door));
+    assert!(map.is_visible(a, b));
+    assert!(map.can_move(door + Vec2Int::LEFT, door, &rules));
+    assert_eq!(map.to_string().lines().nth(1), Some("#..'..#"));
+}
+
+#[test]
+fn test_tile_changes() {
+    let (mut map, _) = Map::from_ascii("###\n#+#\n###").unwrap();
+    let door = Vec2Int::new(1, 1);
+    assert!(map.open_door(door));
+    map.set_tile(door, TileType::Door { open: true });
+    map.set_tile(door, TileType::Rubble);
+    assert_eq!(
+        map.take_changes(),
+        vec![
+            TileChanged {
+                position: door,
+                old: TileType::Door { open: false },
+                new: TileType::Door { open: true },
+            },
+            TileChanged {
+                position: door,
+                old: TileType::Door { open: true },
+                new: TileType::Rubble,
+            },
+        ]
+    );
+    assert!(!map.has_changes());
+}
+
+#[test]
+fn test_digging() {
+    let (mut map, _) = Map::from_ascii(
+        "
+        #####
+        #...#
+        #####",
+    )
+    .unwrap();
+    let wall = Vec2Int::new(2, 0);
+    assert!(!map.is_diggable(wall));
+    let wall = Vec2Int::new(2, 1);
+    map.set_tile(wall, TileType::Wall);
+    map.take_changes();
+    assert!(map.is_diggable(wall));
+    for _ in 0..TileType::Wall.properties().dig_turns.unwrap() - 1 {
+        map.dig(wall);
+        assert!(!map.has_changes());
+    }
+    map.dig(wall);
+    assert_eq!(map.tiles[map.xy_idx(wall.x, wall.y)], TileType::Rubble);
+    assert!(!map.is_diggable(wall));
+}
+
+#[test]
+fn test_traps() {
+    let map = Map::generate(&[]).unwrap();
+    let start = &map.rooms[0];
+    for (position, spawn) in &map.spawns {
+        if !matches!(spawn, Spawn::Trap(_)) {
+            continue;
+        }
+        assert!(
+            !map.tiles[map.xy_idx(position.x, position.y)]
+                .properties()
+                .blocks_movement
+        );
+        assert!(
+            !(start.x..=start.x2 + 1).contains(&position.x)
+                || !(start.y..=start.y2 + 1).contains(&position.y)
+        );
+    }
+}
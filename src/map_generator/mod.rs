@@ -1,58 +1,82 @@
-use bevy::prelude::*;
+use bevy::{app::AppExit, asset::io::file::FileAssetReader, prelude::*};
 
-use crate::common::{resources::CharsetAsset, states::GameState, TileType, HEIGHT, WIDTH, Vec2Int, ToWorld};
+use crate::common::{resources::CharsetAsset, states::GameState};
 
-pub use self::map::Map;
-use self::viewshed::{check_player_viewshed, render_player_viewshed};
+pub use self::map::{Map, TileChanged};
+use self::lighting::{spawn_braziers, update_lighting, LightMap};
+use self::prefab::Prefabs;
+use self::tilemap::{mark_dirty_chunks, spawn_tilemap, update_chunk_meshes};
+use self::viewshed::{invalidate_viewsheds, update_map_visibility, update_viewsheds, MapVisibility};
 
+pub mod lighting;
 mod map;
+mod prefab;
+pub mod tilemap;
 pub mod viewshed;
 
 pub struct MapGeneratorPlugin;
 
 impl Plugin for MapGeneratorPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::LoadAssets), generate_map)
-            .add_systems(Update, (
-                check_player_viewshed,
-                render_player_viewshed,
-            ));
+        app.init_resource::<Prefabs>()
+            .init_resource::<LightMap>()
+            .add_event::<TileChanged>()
+            .add_systems(Startup, load_prefabs)
+            .add_systems(OnEnter(GameState::LoadAssets), generate_map)
+            .add_systems(OnEnter(GameState::Setup), spawn_braziers)
+            .add_systems(
+                PostUpdate,
+                (
+                    send_tile_changes,
+                    (invalidate_viewsheds, update_lighting),
+                    update_viewsheds,
+                    update_map_visibility,
+                    mark_dirty_chunks,
+                    update_chunk_meshes,
+                )
+                    .chain()
+                    .run_if(resource_exists::<Map>()),
+            );
     }
 }
 
-#[derive(Component)]
-pub struct Tile(Vec2Int);
-
-fn generate_map(atlas: Res<CharsetAsset>, mut commands: Commands) {
-    let map = Map::new();
-
-    commands
-        .spawn((
-            Name::from("Tilemap"),
-            TransformBundle::default(),
-            InheritedVisibility::default(),
-        ))
-        .with_children(|parent| {
-            for (idx, tile) in map.tiles.clone().iter().enumerate() {
-                let char = match tile {
-                    TileType::Floor => '.',
-                    TileType::Wall => '#',
-                };
-                let (x, y) = map.idx_xy(idx);
-                parent.spawn(SpriteSheetBundle {
-                    texture_atlas: atlas.atlas.clone(),
-                    sprite: TextureAtlasSprite {
-                        custom_size: Some(Vec2::new(1.0, 1.0)),
-                        index: char as usize,
-                        color: Color::rgba(0.0, 0.0, 0.0, 0.0),
-                        ..Default::default()
-                    },
-                    transform: Transform::from_scale(Vec3::new(WIDTH, HEIGHT, 1.0))
-                        .with_translation((x, y).to_world()),
-                    ..Default::default()
-                })
-                .insert(Tile(Vec2Int::new(x, y)));
-            }
-        });
+fn load_prefabs(mut prefabs: ResMut<Prefabs>) {
+    *prefabs = Prefabs::load(&FileAssetReader::get_base_path().join("assets/prefabs"));
+    info!("Loaded {} prefabs", prefabs.0.len());
+}
+
+fn generate_map(
+    charset: Res<CharsetAsset>,
+    atlases: Res<Assets<TextureAtlas>>,
+    prefabs: Res<Prefabs>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut exit: EventWriter<AppExit>,
+    mut commands: Commands,
+) {
+    let map = match Map::generate(&prefabs.0) {
+        Ok(map) => map,
+        Err(error) => {
+            error!("Could not generate a playable map: {}", error);
+            exit.send(AppExit);
+            return;
+        }
+    };
+    debug!("Generated map:\n{}", map);
+
+    let Some(texture) = atlases.get(&charset.atlas).map(|atlas| atlas.texture.clone()) else {
+        error!("The charset was not loaded before the map");
+        exit.send(AppExit);
+        return;
+    };
+    spawn_tilemap(&mut commands, &map, texture, &mut meshes, &mut materials);
+    commands.insert_resource(MapVisibility::new(&map));
     commands.insert_resource(map);
 }
+
+/// Turns the changes recorded by [`Map::set_tile`] into [`TileChanged`] events.
+fn send_tile_changes(mut map: ResMut<Map>, mut events: EventWriter<TileChanged>) {
+    if map.has_changes() {
+        events.send_batch(map.take_changes());
+    }
+}
@@ -0,0 +1,324 @@
+use std::{fmt, fs, path::Path};
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::common::{
+    spawns::{ItemKind, Monster, Spawn},
+    TileType,
+};
+
+/// One cell of a prefab, as given by its legend character.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cell {
+    /// A space: leaves whatever the generator put there.
+    Keep,
+    Tile(TileType),
+    /// A floor tile with something placed on it.
+    Spawn(Spawn),
+}
+
+/// Tiles use their own glyph where it is unambiguous, water, deep water and lava are `~`, `W`
+/// and `L`. Monsters and items use their glyph and stand on floor, so does a brazier (`*`).
+fn legend(c: char) -> Option<Cell> {
+    let cell = match c {
+        ' ' => Cell::Keep,
+        '#' => Cell::Tile(TileType::Wall),
+        '.' => Cell::Tile(TileType::Floor),
+        '+' => Cell::Tile(TileType::Door { open: false }),
+        '\'' => Cell::Tile(TileType::Door { open: true }),
+        '~' => Cell::Tile(TileType::Water),
+        'W' => Cell::Tile(TileType::DeepWater),
+        'L' => Cell::Tile(TileType::Lava),
+        ':' => Cell::Tile(TileType::Chasm),
+        '%' => Cell::Tile(TileType::Rubble),
+        '"' => Cell::Tile(TileType::Grass),
+        'O' => Cell::Tile(TileType::Pillar),
+        '&' => Cell::Tile(TileType::Statue),
+        'g' => Cell::Spawn(Spawn::Monster(Monster::Goblin)),
+        'o' => Cell::Spawn(Spawn::Monster(Monster::Orc)),
+        'h' => Cell::Spawn(Spawn::Monster(Monster::Dwarf)),
+        'b' => Cell::Spawn(Spawn::Monster(Monster::FireBeetle)),
+        '!' => Cell::Spawn(Spawn::Item(ItemKind::Potion)),
+        '$' => Cell::Spawn(Spawn::Item(ItemKind::Gold)),
+        '(' => Cell::Spawn(Spawn::Item(ItemKind::Pickaxe)),
+        '*' => Cell::Spawn(Spawn::Brazier),
+        _ => return None,
+    };
+    Some(cell)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Placement {
+    /// Replaces the inside of one of the generated rooms.
+    Room,
+    /// Dug into unused rock and tunnelled to the closest room.
+    Open,
+}
+
+/// A hand-drawn piece of map, loaded from `assets/prefabs/`.
+///
+/// A prefab file starts with optional `key: value` lines, followed by a blank line and the
+/// drawing itself, top row first:
+///
+/// ```text
+/// placement: open
+/// chance: 0.5
+///
+/// #####
+/// #$!$#
+/// ##.##
+/// ```
+///
+/// `placement` is `room` (default) or `open`, `chance` is the probability that the generator
+/// tries to place it, `rotate` and `mirror` (both `true` by default) allow the generator to
+/// turn and flip it.
+#[derive(Clone, Debug)]
+pub struct Prefab {
+    pub name: String,
+    pub placement: Placement,
+    pub chance: f64,
+    pub rotate: bool,
+    pub mirror: bool,
+    width: i32,
+    height: i32,
+    cells: Vec<Cell>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PrefabError {
+    Empty,
+    UnknownKey(String),
+    InvalidValue { key: String, value: String },
+    UnknownCell(char),
+}
+
+impl fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrefabError::Empty => write!(f, "the prefab has no cells"),
+            PrefabError::UnknownKey(key) => write!(f, "unknown key `{}`", key),
+            PrefabError::InvalidValue { key, value } => {
+                write!(f, "invalid value `{}` for `{}`", value, key)
+            }
+            PrefabError::UnknownCell(c) => write!(f, "no legend entry for `{}`", c),
+        }
+    }
+}
+
+impl Prefab {
+    pub fn parse(name: &str, text: &str) -> Result<Self, PrefabError> {
+        let mut prefab = Prefab {
+            name: name.to_string(),
+            placement: Placement::Room,
+            chance: 1.0,
+            rotate: true,
+            mirror: true,
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+        };
+
+        let mut lines = text.lines().peekable();
+        // A chasm is drawn as `:` as well, so only `word:` starts a header.
+        let has_header = lines.peek().is_some_and(|line| {
+            line.split_once(':').is_some_and(|(key, _)| {
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_lowercase())
+            })
+        });
+        if has_header {
+            for line in lines.by_ref().take_while(|line| !line.trim().is_empty()) {
+                let (key, value) = line.split_once(':').unwrap_or((line, ""));
+                prefab.set(key.trim(), value.trim())?;
+            }
+        }
+
+        let mut rows: Vec<&str> = lines.collect();
+        while rows.last().is_some_and(|row| row.trim().is_empty()) {
+            rows.pop();
+        }
+        let leading = rows.iter().take_while(|row| row.trim().is_empty()).count();
+        let rows = &rows[leading..];
+
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        if width == 0 {
+            return Err(PrefabError::Empty);
+        }
+        for row in rows {
+            let mut cells = row
+                .chars()
+                .map(|c| legend(c).ok_or(PrefabError::UnknownCell(c)))
+                .collect::<Result<Vec<_>, _>>()?;
+            cells.resize(width, Cell::Keep);
+            prefab.cells.extend(cells);
+        }
+        prefab.width = width as i32;
+        prefab.height = rows.len() as i32;
+        Ok(prefab)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), PrefabError> {
+        let invalid = || PrefabError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        match key {
+            "placement" => {
+                self.placement = match value {
+                    "room" => Placement::Room,
+                    "open" => Placement::Open,
+                    _ => return Err(invalid()),
+                }
+            }
+            "chance" => {
+                self.chance = value
+                    .parse()
+                    .ok()
+                    .filter(|chance| (0.0..=1.0).contains(chance))
+                    .ok_or_else(invalid)?
+            }
+            "rotate" => self.rotate = value.parse().map_err(|_| invalid())?,
+            "mirror" => self.mirror = value.parse().map_err(|_| invalid())?,
+            _ => return Err(PrefabError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// The cell in `column` and `row`, counted from the top left corner of the drawing.
+    pub fn cell(&self, column: i32, row: i32) -> Cell {
+        self.cells[(row * self.width + column) as usize]
+    }
+
+    /// Turns the prefab by 90 degrees clockwise.
+    fn rotated(&self) -> Self {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for row in 0..self.width {
+            for column in 0..self.height {
+                cells.push(self.cell(row, self.height - 1 - column));
+            }
+        }
+        Prefab {
+            width: self.height,
+            height: self.width,
+            cells,
+            ..self.clone()
+        }
+    }
+
+    /// Flips the prefab left to right.
+    fn mirrored(&self) -> Self {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for row in 0..self.height {
+            for column in 0..self.width {
+                cells.push(self.cell(self.width - 1 - column, row));
+            }
+        }
+        Prefab {
+            cells,
+            ..self.clone()
+        }
+    }
+
+    /// A copy turned and flipped at random, as far as the prefab allows it.
+    pub fn randomly_oriented(&self, rng: &mut impl Rng) -> Self {
+        let mut prefab = self.clone();
+        if self.rotate {
+            for _ in 0..rng.gen_range(0..4) {
+                prefab = prefab.rotated();
+            }
+        }
+        if self.mirror && rng.gen_bool(0.5) {
+            prefab = prefab.mirrored();
+        }
+        prefab
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Prefabs(pub Vec<Prefab>);
+
+impl Prefabs {
+    /// Reads every `.txt` file in `directory`. Broken prefabs are skipped with a warning.
+    pub fn load(directory: &Path) -> Self {
+        let Ok(entries) = fs::read_dir(directory) else {
+            warn!("No prefabs found in {}", directory.display());
+            return Self::default();
+        };
+
+        let mut prefabs = Vec::new();
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().and_then(|extension| extension.to_str()) != Some("txt") {
+                continue;
+            }
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            let parsed = fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|text| Prefab::parse(name, &text).map_err(|error| error.to_string()));
+            match parsed {
+                Ok(prefab) => prefabs.push(prefab),
+                Err(error) => warn!("Skipping prefab {}: {}", path.display(), error),
+            }
+        }
+        Self(prefabs)
+    }
+}
+
+#[test]
+fn test_parse_and_orient() {
+    let prefab = Prefab::parse(
+        "test",
+        "placement: open
+rotate: false
+
+##.
+#g
+",
+    )
+    .unwrap();
+    assert_eq!(prefab.placement, Placement::Open);
+    assert!(!prefab.rotate && prefab.mirror);
+    assert_eq!((prefab.width(), prefab.height()), (3, 2));
+    assert_eq!(prefab.cell(2, 0), Cell::Tile(TileType::Floor));
+    assert_eq!(
+        prefab.cell(1, 1),
+        Cell::Spawn(Spawn::Monster(Monster::Goblin))
+    );
+    assert_eq!(prefab.cell(2, 1), Cell::Keep);
+
+    let rotated = prefab.rotated();
+    assert_eq!((rotated.width(), rotated.height()), (2, 3));
+    assert_eq!(rotated.cell(1, 2), Cell::Tile(TileType::Floor));
+    assert_eq!(
+        rotated.cell(0, 1),
+        Cell::Spawn(Spawn::Monster(Monster::Goblin))
+    );
+    let mirrored = prefab.mirrored();
+    assert_eq!(mirrored.cell(0, 0), Cell::Tile(TileType::Floor));
+
+    assert_eq!(
+        Prefab::parse("test", "#x#").err(),
+        Some(PrefabError::UnknownCell('x'))
+    );
+    assert_eq!(
+        Prefab::parse("test", "chance: 2\n\n#").err(),
+        Some(PrefabError::InvalidValue {
+            key: "chance".to_string(),
+            value: "2".to_string()
+        })
+    );
+}
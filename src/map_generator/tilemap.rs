@@ -0,0 +1,413 @@
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::HashSet,
+};
+use fixedbitset::FixedBitSet;
+
+use crate::common::{
+    components::RenderLayer, cp437, resources::CharsetAsset, ToWorld, Vec2Int, HEIGHT, WIDTH,
+};
+
+use super::{lighting::LightMap, viewshed::MapVisibility, Map, TileChanged};
+
+/// Tiles along each side of a chunk.
+pub const CHUNK_SIZE: i32 = 16;
+/// The CP437 glyph that fills the whole cell, drawn behind every tile in its background color.
+pub const FULL_BLOCK: usize = 219;
+/// Brightness of tiles in view that only the viewer's closeness reveals.
+const UNLIT_SHADE: f32 = 0.4;
+/// Brightness of tiles that are remembered but out of view.
+const REMEMBERED_SHADE: f32 = 0.3;
+
+/// The glyph and colors a tile is drawn with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileAppearance {
+    pub glyph: char,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl TileAppearance {
+    /// Tiles in view get the colors of their tile type, as bright as they are lit. Remembered
+    /// ones are drawn in a flat dark shade of them, tiles never seen are not drawn at all.
+    pub fn of(
+        map: &Map,
+        light: &LightMap,
+        position: Vec2Int,
+        revealed: bool,
+        in_view: bool,
+    ) -> Option<Self> {
+        if !revealed {
+            return None;
+        }
+        let brightness = if in_view {
+            UNLIT_SHADE + (1.0 - UNLIT_SHADE) * light.level(map, position)
+        } else {
+            REMEMBERED_SHADE
+        };
+        let shade = |color: Color| {
+            Color::rgb(
+                color.r() * brightness,
+                color.g() * brightness,
+                color.b() * brightness,
+            )
+        };
+        let properties = map.tiles[map.xy_idx(position.x, position.y)].properties();
+        Some(Self {
+            glyph: properties.glyph,
+            fg: shade(properties.fg),
+            bg: shade(properties.bg),
+        })
+    }
+}
+
+/// A square of tiles drawn as a single mesh, rebuilt only when something in it changed.
+#[derive(Component)]
+pub struct TileChunk {
+    origin: Vec2Int,
+    dirty: bool,
+}
+
+/// The chunk entities row by row, starting in the bottom left corner of the map.
+#[derive(Resource)]
+pub struct TileChunks {
+    entities: Vec<Entity>,
+    columns: i32,
+}
+
+impl TileChunks {
+    fn containing(&self, position: Vec2Int) -> Entity {
+        let column = position.x / CHUNK_SIZE;
+        let row = position.y / CHUNK_SIZE;
+        self.entities[(row * self.columns + column) as usize]
+    }
+}
+
+/// Spawns the chunks that draw the map. Their meshes start out empty and are built on the first
+/// update.
+pub fn spawn_tilemap(
+    commands: &mut Commands,
+    map: &Map,
+    texture: Handle<Image>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    let material = materials.add(ColorMaterial::from(texture));
+    let columns = (map.width + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    let rows = (map.height + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    let mut entities = Vec::new();
+    commands
+        .spawn((
+            Name::from("Tilemap"),
+            TransformBundle::default(),
+            InheritedVisibility::default(),
+        ))
+        .with_children(|parent| {
+            for row in 0..rows {
+                for column in 0..columns {
+                    let origin = Vec2Int::new(column * CHUNK_SIZE, row * CHUNK_SIZE);
+                    let chunk = parent
+                        .spawn(MaterialMesh2dBundle {
+                            mesh: meshes.add(empty_mesh()).into(),
+                            material: material.clone(),
+                            transform: Transform::from_translation(
+                                (origin.x, origin.y).to_world(RenderLayer::Terrain.z()),
+                            ),
+                            visibility: Visibility::Hidden,
+                            ..Default::default()
+                        })
+                        .insert(TileChunk {
+                            origin,
+                            dirty: true,
+                        })
+                        .id();
+                    entities.push(chunk);
+                }
+            }
+        });
+    commands.insert_resource(TileChunks { entities, columns });
+}
+
+/// Marks the chunks with tiles that changed, came into view, left it or were lit differently.
+pub fn mark_dirty_chunks(
+    map: Res<Map>,
+    chunks: Res<TileChunks>,
+    light: Res<LightMap>,
+    fog: Res<MapVisibility>,
+    mut drawn_visible: Local<FixedBitSet>,
+    mut changes: EventReader<TileChanged>,
+    mut dirty: Query<&mut TileChunk>,
+) {
+    let mut positions: Vec<Vec2Int> = changes.read().map(|change| change.position).collect();
+    if fog.is_changed() || light.is_changed() {
+        let mut indices: Vec<usize> = fog.visible.symmetric_difference(&drawn_visible).collect();
+        if light.is_changed() {
+            indices.extend(fog.visible.ones());
+        }
+        positions.extend(indices.into_iter().map(|idx| {
+            let (x, y) = map.idx_xy(idx);
+            Vec2Int::new(x, y)
+        }));
+        drawn_visible.clone_from(&fog.visible);
+    }
+
+    let marked: HashSet<Entity> = positions
+        .into_iter()
+        .map(|position| chunks.containing(position))
+        .collect();
+    let mut chunks = dirty.iter_many_mut(marked);
+    while let Some(mut chunk) = chunks.fetch_next() {
+        chunk.dirty = true;
+    }
+}
+
+/// Rebuilds the meshes of dirty chunks. Chunks without a single seen tile are hidden.
+pub fn update_chunk_meshes(
+    map: Res<Map>,
+    light: Res<LightMap>,
+    charset: Res<CharsetAsset>,
+    atlases: Res<Assets<TextureAtlas>>,
+    fog: Res<MapVisibility>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut chunks: Query<(&mut TileChunk, &Mesh2dHandle, &mut Visibility)>,
+) {
+    let Some(atlas) = atlases.get(&charset.atlas) else {
+        return;
+    };
+    for (mut chunk, handle, mut visibility) in &mut chunks {
+        if !chunk.dirty {
+            continue;
+        }
+        chunk.dirty = false;
+        let mesh = build_chunk_mesh(&map, &light, &fog, atlas, chunk.origin);
+        *visibility = if mesh.count_vertices() == 0 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+        if let Some(old) = meshes.get_mut(&handle.0) {
+            *old = mesh;
+        }
+    }
+}
+
+fn empty_mesh() -> Mesh {
+    GlyphMesh::default().build()
+}
+
+/// Collects colored glyph quads from the atlas into a single mesh.
+#[derive(Default)]
+pub struct GlyphMesh {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl GlyphMesh {
+    pub fn push(
+        &mut self,
+        atlas: &TextureAtlas,
+        glyph: usize,
+        center: Vec2,
+        size: Vec2,
+        color: Color,
+    ) {
+        let rect = atlas.textures[glyph];
+        let [u0, u1] = [rect.min.x, rect.max.x].map(|u| u / atlas.size.x);
+        let [v0, v1] = [rect.min.y, rect.max.y].map(|v| v / atlas.size.y);
+        let [min, max] = [center - size / 2.0, center + size / 2.0];
+        let first = self.positions.len() as u32;
+        self.positions.extend([
+            [min.x, min.y, 0.0],
+            [max.x, min.y, 0.0],
+            [max.x, max.y, 0.0],
+            [min.x, max.y, 0.0],
+        ]);
+        self.uvs.extend([[u0, v1], [u1, v1], [u1, v0], [u0, v0]]);
+        self.colors.extend([color.as_linear_rgba_f32(); 4]);
+        self.indices
+            .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    pub fn build(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+/// Two quads per seen tile, the background block first so the glyph is blended on top of it.
+fn build_chunk_mesh(
+    map: &Map,
+    light: &LightMap,
+    fog: &MapVisibility,
+    atlas: &TextureAtlas,
+    origin: Vec2Int,
+) -> Mesh {
+    let mut mesh = GlyphMesh::default();
+    for y in origin.y..(origin.y + CHUNK_SIZE).min(map.height) {
+        for x in origin.x..(origin.x + CHUNK_SIZE).min(map.width) {
+            let position = Vec2Int::new(x, y);
+            let revealed = fog.is_revealed(map, position);
+            let in_view = fog.is_visible(map, position);
+            let Some(appearance) = TileAppearance::of(map, light, position, revealed, in_view)
+            else {
+                continue;
+            };
+            let center = Vec2::new(
+                (x - origin.x) as f32 * WIDTH,
+                (y - origin.y) as f32 * HEIGHT,
+            );
+            let size = Vec2::new(WIDTH, HEIGHT);
+            mesh.push(atlas, FULL_BLOCK, center, size, appearance.bg);
+            mesh.push(
+                atlas,
+                cp437::to_index(appearance.glyph),
+                center,
+                size,
+                appearance.fg,
+            );
+        }
+    }
+    mesh.build()
+}
+
+#[test]
+fn test_chunk_mesh() {
+    let (map, _) = Map::from_ascii(
+        "
+        #####
+        #...#
+        #####",
+    )
+    .unwrap();
+    let atlas = TextureAtlas::from_grid(Handle::default(), Vec2::new(8.0, 8.0), 16, 16, None, None);
+    let light = LightMap::compute(&map, std::iter::empty());
+    let mut fog = MapVisibility::new(&map);
+    for x in 0..5 {
+        fog.revealed.insert(map.xy_idx(x, 1));
+    }
+    fog.visible.insert(map.xy_idx(2, 1));
+    let mesh = build_chunk_mesh(&map, &light, &fog, &atlas, Vec2Int::new(0, 0));
+    // A background and a glyph quad for each of the five seen tiles.
+    assert_eq!(mesh.count_vertices(), 5 * 2 * 4);
+    let remembered = TileAppearance::of(&map, &light, Vec2Int::new(1, 1), true, false).unwrap();
+    let in_view = TileAppearance::of(&map, &light, Vec2Int::new(2, 1), true, true).unwrap();
+    assert!(remembered.fg.r() < in_view.fg.r());
+    assert_eq!(
+        TileAppearance::of(&map, &light, Vec2Int::new(1, 1), false, false),
+        None
+    );
+}
+
+/// Compares recoloring one sprite entity per tile every frame, the way tiles used to be drawn,
+/// with rebuilding only the chunks a moving viewer touches. Run it with
+/// `cargo test --release bench_tilemap -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_tilemap() {
+    use std::time::Instant;
+
+    #[derive(Component)]
+    struct Tile(Vec2Int);
+    #[derive(Component)]
+    struct Visited;
+    #[derive(Component)]
+    struct InRange;
+    #[derive(Component)]
+    struct Background;
+
+    fn recolor_sprites(
+        map: Res<Map>,
+        light: Res<LightMap>,
+        mut tiles: Query<(&Tile, &mut TextureAtlasSprite, Has<Visited>, Has<InRange>)>,
+        mut backgrounds: Query<
+            (&Parent, &mut TextureAtlasSprite),
+            (With<Background>, Without<Tile>),
+        >,
+    ) {
+        for (tile, mut sprite, visited, in_range) in &mut tiles {
+            if let Some(appearance) = TileAppearance::of(&map, &light, tile.0, visited, in_range) {
+                sprite.color = appearance.fg;
+            }
+        }
+        for (parent, mut sprite) in &mut backgrounds {
+            let Ok((tile, _, visited, in_range)) = tiles.get(parent.get()) else {
+                continue;
+            };
+            if let Some(appearance) = TileAppearance::of(&map, &light, tile.0, visited, in_range) {
+                sprite.color = appearance.bg;
+            }
+        }
+    }
+
+    const FRAMES: u32 = 200;
+    let map = Map::generate(&[]).unwrap();
+    let light = LightMap::compute(&map, std::iter::empty());
+    let viewer = map.start().unwrap();
+    let in_view = |position: Vec2Int| position.distance(&viewer) < 8.0;
+    let mut fog = MapVisibility::new(&map);
+    fog.revealed.insert_range(..);
+    for idx in 0..map.len() {
+        let (x, y) = map.idx_xy(idx);
+        fog.visible.set(idx, in_view(Vec2Int::new(x, y)));
+    }
+
+    // A viewer walking back and forth dirties the chunks its view overlaps, at most four.
+    let atlas = TextureAtlas::from_grid(Handle::default(), Vec2::new(8.0, 8.0), 16, 16, None, None);
+    let mut meshes = Assets::<Mesh>::default();
+    let mut handles = bevy::utils::HashMap::new();
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        let moved = viewer + Vec2Int::new((frame % 4) as i32, 0);
+        let touched: HashSet<Vec2Int> = [(-8, -8), (8, -8), (-8, 8), (8, 8)]
+            .into_iter()
+            .map(|(x, y)| moved + Vec2Int::new(x, y))
+            .filter(|corner| map.in_bounds(*corner))
+            .map(|corner| {
+                Vec2Int::new(
+                    corner.x / CHUNK_SIZE * CHUNK_SIZE,
+                    corner.y / CHUNK_SIZE * CHUNK_SIZE,
+                )
+            })
+            .collect();
+        for origin in touched {
+            let mesh = build_chunk_mesh(&map, &light, &fog, &atlas, origin);
+            let handle = handles
+                .entry((origin.x / CHUNK_SIZE, origin.y / CHUNK_SIZE))
+                .or_insert_with(|| meshes.add(empty_mesh()));
+            *meshes.get_mut(&*handle).unwrap() = mesh;
+        }
+    }
+    let chunks = start.elapsed();
+
+    let mut world = World::new();
+    for idx in 0..map.len() {
+        let (x, y) = map.idx_xy(idx);
+        let position = Vec2Int::new(x, y);
+        let mut tile = world.spawn((Tile(position), TextureAtlasSprite::default(), Visited));
+        if in_view(position) {
+            tile.insert(InRange);
+        }
+        tile.with_children(|tile| {
+            tile.spawn((TextureAtlasSprite::new(FULL_BLOCK), Background));
+        });
+    }
+    world.insert_resource(map);
+    world.insert_resource(light);
+    let mut schedule = Schedule::default();
+    schedule.add_systems(recolor_sprites);
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        schedule.run(&mut world);
+    }
+    let sprites = start.elapsed();
+
+    println!("{FRAMES} frames: one sprite per tile {sprites:?}, dirty chunk meshes {chunks:?}");
+}
@@ -1,48 +1,113 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashSet};
+use fixedbitset::FixedBitSet;
 
-use crate::{common::components::Position, player::Player};
+use crate::{common::{components::Position, Vec2Int}, player::Player};
 
-use super::{Tile, Map};
+use super::{lighting::LightMap, TileChanged, Map};
 
 #[derive(Component)]
 pub struct Viewshed {
     pub range: f32,
+    /// Every tile that could be seen from the current position the last time it was computed.
+    pub visible: HashSet<Vec2Int>,
+    /// Set when something in range changed and `visible` has to be computed again.
+    pub dirty: bool,
 }
 
-#[derive(Component)]
-pub struct Visited;
+impl Viewshed {
+    pub fn new(range: f32) -> Self {
+        Self {
+            range,
+            visible: HashSet::new(),
+            dirty: true,
+        }
+    }
 
-#[derive(Component)]
-pub struct InRange;
+    pub fn can_see(&self, map: &Map, from: Vec2Int, to: Vec2Int) -> bool {
+        from.distance(&to) < self.range && map.is_visible(from, to)
+    }
 
-pub fn check_player_viewshed(
-    map: ResMut<Map>,
-    players: Query<(&Position, &Viewshed), With<Player>>,
-    tiles: Query<(Entity, &Tile)>,
-    mut commands: Commands,
-) {
-    let Ok((player, viewshed)) = players.get_single() else {
-        return;
-    };
+    /// Tiles in the dark are only made out right next to the viewer.
+    fn compute(&self, map: &Map, light: &LightMap, from: Vec2Int) -> HashSet<Vec2Int> {
+        let reach = self.range.ceil() as i32;
+        (-reach..=reach)
+            .flat_map(|y| (-reach..=reach).map(move |x| from + Vec2Int::new(x, y)))
+            .filter(|to| map.in_bounds(*to) && self.can_see(map, from, *to))
+            .filter(|to| from.distance(to) < 1.5 || light.is_lit(map, *to))
+            .collect()
+    }
+}
 
-    for (entity, tile) in &tiles {
-        if tile.0.distance(player) < viewshed.range && map.is_visible(player.0, tile.0) {
-            commands.entity(entity).insert((Visited, InRange));
+/// What the player knows about the map, one bit per tile by map index: the tiles they have
+/// seen at some point and the ones in view right now.
+#[derive(Resource, Default)]
+pub struct MapVisibility {
+    pub revealed: FixedBitSet,
+    pub visible: FixedBitSet,
+}
+
+impl MapVisibility {
+    pub fn new(map: &Map) -> Self {
+        Self {
+            revealed: FixedBitSet::with_capacity(map.len()),
+            visible: FixedBitSet::with_capacity(map.len()),
         }
-        else {
-            commands.entity(entity).remove::<InRange>();
+    }
+
+    /// Whether the player has ever seen the tile.
+    pub fn is_revealed(&self, map: &Map, position: Vec2Int) -> bool {
+        map.in_bounds(position) && self.revealed.contains(map.xy_idx(position.x, position.y))
+    }
+
+    /// Whether the tile is in the player's view right now.
+    pub fn is_visible(&self, map: &Map, position: Vec2Int) -> bool {
+        map.in_bounds(position) && self.visible.contains(map.xy_idx(position.x, position.y))
+    }
+}
+
+/// Marks the viewsheds that a changed tile might be visible from.
+pub fn invalidate_viewsheds(
+    mut changes: EventReader<TileChanged>,
+    mut viewers: Query<(&Position, &mut Viewshed)>,
+) {
+    for change in changes.read() {
+        for (position, mut viewshed) in &mut viewers {
+            if position.0.distance(&change.position) <= viewshed.range {
+                viewshed.dirty = true;
+            }
         }
     }
 }
 
-pub fn render_player_viewshed(
-    mut in_range: Query<&mut TextureAtlasSprite, With<InRange>>,
-    mut visited: Query<&mut TextureAtlasSprite, (With<Visited>, Without<InRange>)>,
+/// Recomputes viewsheds that moved or were invalidated.
+pub fn update_viewsheds(
+    map: Res<Map>,
+    light: Res<LightMap>,
+    mut viewers: Query<(Ref<Position>, &mut Viewshed)>,
 ) {
-    for mut sprite in &mut in_range {
-        sprite.color = Color::WHITE;
+    for (position, mut viewshed) in &mut viewers {
+        if !viewshed.dirty && !position.is_changed() {
+            continue;
+        }
+        viewshed.visible = viewshed.compute(&map, &light, position.0);
+        viewshed.dirty = false;
     }
-    for mut sprite in &mut visited {
-        sprite.color = Color::GRAY;
+}
+
+/// Copies the player's viewshed into [`MapVisibility`] whenever it changed.
+pub fn update_map_visibility(
+    map: Res<Map>,
+    players: Query<&Viewshed, (With<Player>, Changed<Viewshed>)>,
+    mut visibility: ResMut<MapVisibility>,
+) {
+    let Ok(viewshed) = players.get_single() else {
+        return;
+    };
+
+    let visibility = &mut *visibility;
+    visibility.visible.clear();
+    for position in &viewshed.visible {
+        visibility.visible.insert(map.xy_idx(position.x, position.y));
     }
-}
\ No newline at end of file
+    visibility.revealed.union_with(&visibility.visible);
+}
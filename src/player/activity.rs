@@ -0,0 +1,217 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    common::{
+        components::{Health, Mobility, Position},
+        pathfinding::Path,
+        resources::MovementRules,
+        states::GameState,
+        Vec2Int,
+    },
+    enemy::Enemy,
+    item::Item,
+    map_generator::{
+        viewshed::{MapVisibility, Viewshed},
+        Map,
+    },
+    trap::Trap,
+};
+
+use super::Player;
+
+/// Something the player keeps doing over several turns until it is finished or interrupted.
+#[derive(Component)]
+pub enum Activity {
+    /// Keep walking in one direction until something interesting happens.
+    Run(Vec2Int),
+    /// Walk towards the closest tile that has not been seen yet, one step per turn.
+    Explore,
+    /// Follow a path to a chosen destination.
+    Travel(Path),
+    /// Pass turns until fully healed.
+    Rest,
+}
+
+enum Step {
+    /// A turn was spent and the activity goes on.
+    Continue,
+    /// A turn was spent and the activity is done.
+    Last,
+    /// Nothing happened, the activity is done.
+    Stop,
+}
+
+pub fn perform_activity(
+    mut state: ResMut<NextState<GameState>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut map: ResMut<Map>,
+    rules: Res<MovementRules>,
+    mut last_health: Local<i32>,
+    mut seen_items: Local<HashSet<Entity>>,
+    mut players: Query<
+        (
+            Entity,
+            &mut Position,
+            &Viewshed,
+            &mut Health,
+            &Mobility,
+            Option<&mut Activity>,
+        ),
+        With<Player>,
+    >,
+    enemies: Query<&Position, (With<Enemy>, Without<Player>)>,
+    items: Query<(Entity, &Position), (With<Item>, Without<Player>)>,
+    fog: Res<MapVisibility>,
+    traps: Query<(Ref<Trap>, &Position), Without<Player>>,
+    mut commands: Commands,
+) {
+    let Ok((entity, mut position, viewshed, mut health, mobility, activity)) =
+        players.get_single_mut()
+    else {
+        return;
+    };
+    let hurt = health.current < *last_health;
+    *last_health = health.current;
+    // Items are remembered even while no activity runs, so only ones that were never seen
+    // before interrupt.
+    let mut new_item_in_sight = false;
+    for (item, item_position) in &items {
+        if viewshed.visible.contains(&item_position.0) && seen_items.insert(item) {
+            new_item_in_sight = true;
+        }
+    }
+    let Some(mut activity) = activity else {
+        return;
+    };
+
+    let enemy_in_sight = enemies
+        .iter()
+        .any(|enemy| viewshed.visible.contains(&enemy.0));
+    let trap_found = traps
+        .iter()
+        .any(|(trap, _)| trap.detected && trap.is_changed());
+    let known_traps: HashSet<Vec2Int> = traps
+        .iter()
+        .filter(|(trap, _)| trap.detected)
+        .map(|(_, position)| position.0)
+        .collect();
+    let step = if hurt
+        || enemy_in_sight
+        || trap_found
+        || new_item_in_sight
+        || keyboard_input.get_just_pressed().next().is_some()
+    {
+        Step::Stop
+    } else {
+        match &mut *activity {
+            Activity::Run(direction) => {
+                run(&map, &rules, &mut position.0, *direction, &known_traps)
+            }
+            Activity::Explore => explore(
+                &mut map,
+                &rules,
+                mobility.without_digging(),
+                &mut position.0,
+                &fog,
+                &known_traps,
+            ),
+            Activity::Travel(path) => travel(&mut map, &rules, &mut position.0, path),
+            Activity::Rest => rest(&mut health),
+        }
+    };
+
+    match step {
+        Step::Continue => state.set(GameState::EnemyTurn),
+        Step::Last => {
+            state.set(GameState::EnemyTurn);
+            commands.entity(entity).remove::<Activity>();
+        }
+        Step::Stop => {
+            commands.entity(entity).remove::<Activity>();
+        }
+    }
+}
+
+fn run(
+    map: &Map,
+    rules: &MovementRules,
+    position: &mut Vec2Int,
+    direction: Vec2Int,
+    known_traps: &HashSet<Vec2Int>,
+) -> Step {
+    let next = *position + direction;
+    if !map.can_move(*position, next, rules) || known_traps.contains(&next) {
+        return Step::Stop;
+    }
+    let before = side_openings(map, *position, direction);
+    *position = next;
+    if map.tiles[map.xy_idx(next.x, next.y)].is_door()
+        || side_openings(map, next, direction) != before
+    {
+        return Step::Last;
+    }
+    Step::Continue
+}
+
+fn explore(
+    map: &mut Map,
+    rules: &MovementRules,
+    mobility: Mobility,
+    position: &mut Vec2Int,
+    fog: &MapVisibility,
+    known_traps: &HashSet<Vec2Int>,
+) -> Step {
+    let path = Path::to_nearest(*position, map, rules, mobility, known_traps, |tile| {
+        !fog.is_revealed(map, tile)
+    });
+    let Some(next) = path.and_then(|mut path| path.waypoints.pop_front()) else {
+        return Step::Stop;
+    };
+    if !map.open_door(next) {
+        *position = next;
+    }
+    Step::Continue
+}
+
+fn travel(map: &mut Map, rules: &MovementRules, position: &mut Vec2Int, path: &mut Path) -> Step {
+    let Some(&next) = path.waypoints.front() else {
+        return Step::Stop;
+    };
+    if map.open_door(next) {
+        return Step::Continue;
+    }
+    path.waypoints.pop_front();
+    if !map.can_move(*position, next, rules) {
+        return Step::Stop;
+    }
+    *position = next;
+    if path.waypoints.is_empty() {
+        return Step::Last;
+    }
+    Step::Continue
+}
+
+fn rest(health: &mut Health) -> Step {
+    if health.current >= health.max {
+        return Step::Stop;
+    }
+    health.current += 1;
+    if health.current >= health.max {
+        return Step::Last;
+    }
+    Step::Continue
+}
+
+/// Which of the tiles to the left and right of `position` are open or doors, seen when facing
+/// `direction`. Diagonal runs have no sides and only end when blocked or interrupted.
+fn side_openings(map: &Map, position: Vec2Int, direction: Vec2Int) -> Option<[bool; 2]> {
+    if direction.is_diagonal() {
+        return None;
+    }
+    let left = position + Vec2Int::new(-direction.y, direction.x);
+    let right = position + Vec2Int::new(direction.y, -direction.x);
+    Some([left, right].map(|side| {
+        let idx = map.xy_idx(side.x, side.y);
+        map.tiles[idx].is_door() || !map.is_occupied(idx)
+    }))
+}
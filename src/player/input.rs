@@ -1,36 +1,418 @@
 use bevy::prelude::*;
+use rand::Rng;
 
 use crate::{
-    common::{components::Position, TileType, Vec2Int, states::GameState},
+    animation::Lunge,
+    common::{
+        components::{Health, Mobility, Position},
+        resources::MovementRules,
+        states::GameState,
+        Vec2Int,
+    },
+    enemy::Enemy,
     map_generator::Map,
+    targeting::{line_of_fire, StartTargeting, TargetChosen},
+    trap::{Trap, TrapChances, TrapTriggered},
 };
 
-use super::Player;
+use super::{activity::Activity, Player};
 
-pub fn move_player(
+/// Tweaks for how held keys are turned into player actions.
+#[derive(Resource)]
+pub struct InputSettings {
+    /// Seconds a movement key has to be held before it starts repeating.
+    pub repeat_delay: f32,
+    /// Seconds between two repeated steps while the key stays held.
+    pub repeat_interval: f32,
+    /// Holding any of these while pressing a direction starts running.
+    pub run_modifiers: Vec<KeyCode>,
+    /// Starts walking towards unexplored parts of the map.
+    pub explore: KeyCode,
+    /// Any of these passes a single turn.
+    pub wait: Vec<KeyCode>,
+    /// Rests until fully healed or disturbed.
+    pub rest: KeyCode,
+    /// Spends a turn looking for hidden traps on the surrounding tiles.
+    pub search: KeyCode,
+    /// Spends a turn trying to disarm a known trap next to or below the player.
+    pub disarm: KeyCode,
+    /// Aims a stone to throw, see [`THROW_RANGE`].
+    pub throw: KeyCode,
+    /// Looks around the map with a cursor, without spending turns.
+    pub look: KeyCode,
+    /// Jumps the cursor to the next visible monster or item while looking or aiming.
+    pub next_target: KeyCode,
+    /// Whether walking into a wall spends the turn.
+    pub wall_bump_passes_turn: bool,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            repeat_delay: 0.3,
+            repeat_interval: 0.1,
+            run_modifiers: vec![KeyCode::ShiftLeft, KeyCode::ShiftRight],
+            explore: KeyCode::O,
+            wait: vec![KeyCode::Space, KeyCode::Numpad5],
+            rest: KeyCode::R,
+            search: KeyCode::F,
+            disarm: KeyCode::T,
+            throw: KeyCode::V,
+            look: KeyCode::L,
+            next_target: KeyCode::Space,
+            wall_bump_passes_turn: false,
+        }
+    }
+}
+
+/// Which movement key is held and when it repeats next.
+#[derive(Default)]
+pub struct KeyRepeat {
+    held: Option<Vec2Int>,
+    next_step: f32,
+}
+
+const MOVEMENT_KEYS: [(KeyCode, Vec2Int); 16] = [
+    (KeyCode::A, Vec2Int::LEFT),
+    (KeyCode::D, Vec2Int::RIGHT),
+    (KeyCode::W, Vec2Int::UP),
+    (KeyCode::S, Vec2Int::DOWN),
+    (KeyCode::Q, Vec2Int::UP_LEFT),
+    (KeyCode::E, Vec2Int::UP_RIGHT),
+    (KeyCode::Z, Vec2Int::DOWN_LEFT),
+    (KeyCode::C, Vec2Int::DOWN_RIGHT),
+    (KeyCode::Numpad4, Vec2Int::LEFT),
+    (KeyCode::Numpad6, Vec2Int::RIGHT),
+    (KeyCode::Numpad8, Vec2Int::UP),
+    (KeyCode::Numpad2, Vec2Int::DOWN),
+    (KeyCode::Numpad7, Vec2Int::UP_LEFT),
+    (KeyCode::Numpad9, Vec2Int::UP_RIGHT),
+    (KeyCode::Numpad1, Vec2Int::DOWN_LEFT),
+    (KeyCode::Numpad3, Vec2Int::DOWN_RIGHT),
+];
+
+fn just_pressed_direction(keyboard_input: &Input<KeyCode>) -> Option<Vec2Int> {
+    MOVEMENT_KEYS
+        .iter()
+        .find(|(key, _)| keyboard_input.just_pressed(*key))
+        .map(|(_, direction)| *direction)
+}
+
+/// Returns the direction to step in this frame, repeating held keys after the configured delay.
+pub fn repeated_direction(
+    keyboard_input: &Input<KeyCode>,
+    now: f32,
+    settings: &InputSettings,
+    repeat: &mut KeyRepeat,
+) -> Option<Vec2Int> {
+    if let Some(direction) = just_pressed_direction(keyboard_input) {
+        repeat.held = Some(direction);
+        repeat.next_step = now + settings.repeat_delay;
+        return Some(direction);
+    }
+
+    repeat.held = repeat.held.filter(|held| {
+        MOVEMENT_KEYS
+            .iter()
+            .any(|(key, direction)| direction == held && keyboard_input.pressed(*key))
+    });
+    if repeat.held.is_some() && now >= repeat.next_step {
+        repeat.next_step = now + settings.repeat_interval;
+        return repeat.held;
+    }
+    None
+}
+
+pub fn start_activity(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<InputSettings>,
+    players: Query<Entity, (With<Player>, Without<Activity>)>,
+    mut commands: Commands,
+) {
+    for entity in &players {
+        if keyboard_input.just_pressed(settings.explore) {
+            commands.entity(entity).insert(Activity::Explore);
+        } else if keyboard_input.just_pressed(settings.rest) {
+            commands.entity(entity).insert(Activity::Rest);
+        }
+    }
+}
+
+pub fn wait(
+    mut state: ResMut<NextState<GameState>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<InputSettings>,
+    players: Query<(), (With<Player>, Without<Activity>)>,
+) {
+    if !players.is_empty() && keyboard_input.any_just_pressed(settings.wait.iter().copied()) {
+        state.set(GameState::EnemyTurn);
+    }
+}
+
+pub fn search(
+    mut state: ResMut<NextState<GameState>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<InputSettings>,
+    chances: Res<TrapChances>,
+    players: Query<&Position, (With<Player>, Without<Activity>)>,
+    mut traps: Query<(&mut Trap, &Position, &Name), Without<Player>>,
+) {
+    if !keyboard_input.just_pressed(settings.search) {
+        return;
+    }
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    let mut rng = rand::thread_rng();
+    for (mut trap, position, name) in &mut traps {
+        if !trap.detected && player.0.distance(&position.0) < 1.5 && rng.gen_bool(chances.search) {
+            trap.detected = true;
+            info!("You find a {}", name.to_lowercase());
+        }
+    }
+    state.set(GameState::EnemyTurn);
+}
+
+pub fn disarm(
     mut state: ResMut<NextState<GameState>>,
     keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<InputSettings>,
+    chances: Res<TrapChances>,
+    players: Query<(Entity, &Position), (With<Player>, Without<Activity>)>,
+    traps: Query<(Entity, &Trap, &Position, &Name), Without<Player>>,
+    mut triggered: EventWriter<TrapTriggered>,
+    mut commands: Commands,
+) {
+    if !keyboard_input.just_pressed(settings.disarm) {
+        return;
+    }
+    let Ok((player, player_position)) = players.get_single() else {
+        return;
+    };
+    let Some((trap, _, _, name)) = traps.iter().find(|(_, trap, position, _)| {
+        trap.detected && player_position.0.distance(&position.0) < 1.5
+    }) else {
+        return;
+    };
+    let mut rng = rand::thread_rng();
+    if rng.gen_bool(chances.disarm) {
+        info!("You disarm the {}", name.to_lowercase());
+        commands.entity(trap).despawn();
+    } else if rng.gen_bool(chances.set_off) {
+        triggered.send(TrapTriggered {
+            trap,
+            victim: player,
+        });
+    } else {
+        info!("You fail to disarm the {}", name.to_lowercase());
+    }
+    state.set(GameState::EnemyTurn);
+}
+
+/// How far a stone can be thrown, in tiles.
+pub const THROW_RANGE: f32 = 6.0;
+
+/// Asks for a target when the throw key is pressed and throws a stone at it once one is chosen.
+/// The stone stops at the first creature or wall in its way.
+pub fn throw(
+    mut state: ResMut<NextState<GameState>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<InputSettings>,
+    mut aiming: Local<bool>,
     map: Res<Map>,
-    mut players: Query<&mut Position, With<Player>>,
+    players: Query<(Entity, &Position), (With<Player>, Without<Activity>)>,
+    mut creatures: Query<
+        (Entity, &Position, &Name, Option<&mut Health>),
+        Or<(With<Enemy>, With<Player>)>,
+    >,
+    mut requests: EventWriter<StartTargeting>,
+    mut chosen: EventReader<TargetChosen>,
+) {
+    let Ok((player, from)) = players.get_single() else {
+        return;
+    };
+    // Input only comes back here once aiming is over, with or without a target.
+    if *aiming {
+        *aiming = false;
+        let Some(target) = chosen
+            .read()
+            .filter(|target| target.origin == player)
+            .last()
+        else {
+            return;
+        };
+        if target.tile == from.0 {
+            return;
+        }
+        let is_creature =
+            |tile: Vec2Int| creatures.iter().any(|(_, position, ..)| position.0 == tile);
+        let line = line_of_fire(&map, from.0, target.tile, None, is_creature);
+        let Some(&landing) = line.path.last() else {
+            return;
+        };
+        // The creature aimed at if nothing was in the way, otherwise whoever stopped the stone.
+        let struck = target
+            .entity
+            .filter(|entity| line.blocked_at.is_none() && creatures.contains(*entity))
+            .or_else(|| {
+                creatures
+                    .iter()
+                    .find(|(_, position, ..)| position.0 == landing)
+                    .map(|(entity, ..)| entity)
+            });
+        if let Some(Ok((_, _, name, health))) = struck.map(|entity| creatures.get_mut(entity)) {
+            let damage = rand::thread_rng().gen_range(1..=3);
+            info!("The stone hits the {}", name.to_lowercase());
+            if let Some(mut health) = health {
+                health.current -= damage;
+                info!("{} takes {} damage", name, damage);
+            }
+        } else {
+            let tile = map.tiles[map.xy_idx(landing.x, landing.y)].properties();
+            let verb = if line.blocked_at.is_some() {
+                "hits"
+            } else {
+                "lands on"
+            };
+            info!("The stone {} the {}", verb, tile.description);
+        }
+        state.set(GameState::EnemyTurn);
+        return;
+    }
+    if keyboard_input.just_pressed(settings.throw) {
+        *aiming = true;
+        requests.send(StartTargeting {
+            origin: player,
+            range: Some(THROW_RANGE),
+        });
+    }
+}
+
+pub fn move_player(
+    mut state: ResMut<NextState<GameState>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    settings: Res<InputSettings>,
+    mut repeat: Local<KeyRepeat>,
+    mut map: ResMut<Map>,
+    rules: Res<MovementRules>,
+    mut players: Query<(Entity, &mut Position, &Mobility), (With<Player>, Without<Activity>)>,
+    mut lunges: EventWriter<Lunge>,
+    mut commands: Commands,
 ) {
-    for mut position in &mut players {
-        let mut direction = Vec2Int::ZERO;
-        if keyboard_input.just_pressed(KeyCode::A) {
-            direction = Vec2Int::LEFT;
-        } else if keyboard_input.just_pressed(KeyCode::D) {
-            direction = Vec2Int::RIGHT;
-        } else if keyboard_input.just_pressed(KeyCode::W) {
-            direction = Vec2Int::UP;
-        } else if keyboard_input.just_pressed(KeyCode::S) {
-            direction = Vec2Int::DOWN;
+    let Some(direction) = repeated_direction(
+        &keyboard_input,
+        time.elapsed_seconds(),
+        &settings,
+        &mut repeat,
+    ) else {
+        return;
+    };
+
+    for (entity, mut position, mobility) in &mut players {
+        if keyboard_input.any_pressed(settings.run_modifiers.iter().copied()) {
+            commands.entity(entity).insert(Activity::Run(direction));
+            continue;
         }
         let new_pos: Vec2Int = direction + position.0;
-        let idx = map.xy_idx(new_pos.x, new_pos.y);
-        if map.tiles[idx] == TileType::Floor {
+        if mobility.opens_doors && map.open_door(new_pos) {
+            // Opening the door takes the turn, stepping through it is the next one.
+        } else if mobility.digs && map.is_diggable(new_pos) {
+            map.dig(new_pos);
+            lunges.send(Lunge {
+                entity,
+                toward: new_pos,
+            });
+        } else if map.can_move(position.0, new_pos, &rules) {
             position.0 = new_pos;
+        } else if !settings.wall_bump_passes_turn {
+            continue;
         }
-        if direction != Vec2Int::ZERO {
-            state.set(GameState::EnemyTurn);
-        }
+        state.set(GameState::EnemyTurn);
     }
 }
+
+#[test]
+fn test_key_repeat() {
+    let settings = InputSettings::default();
+    let mut repeat = KeyRepeat::default();
+    let mut keyboard_input = Input::<KeyCode>::default();
+
+    keyboard_input.press(KeyCode::D);
+    let direction = repeated_direction(&keyboard_input, 0.0, &settings, &mut repeat);
+    assert_eq!(direction, Some(Vec2Int::RIGHT));
+
+    keyboard_input.clear();
+    let direction = repeated_direction(&keyboard_input, 0.1, &settings, &mut repeat);
+    assert_eq!(direction, None);
+    let direction = repeated_direction(
+        &keyboard_input,
+        settings.repeat_delay,
+        &settings,
+        &mut repeat,
+    );
+    assert_eq!(direction, Some(Vec2Int::RIGHT));
+
+    keyboard_input.release(KeyCode::D);
+    let direction = repeated_direction(&keyboard_input, 10.0, &settings, &mut repeat);
+    assert_eq!(direction, None);
+}
+
+#[test]
+fn test_disarm() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::common::spawns::TrapKind;
+
+    let (map, markers) = crate::map_generator::Map::from_ascii(
+        "
+        ######
+        #^@^.#
+        ######",
+    )
+    .unwrap();
+    let settings = InputSettings::default();
+    let mut keyboard_input = Input::<KeyCode>::default();
+    keyboard_input.press(settings.disarm);
+    let mut world = World::new();
+    world.insert_resource(map);
+    world.insert_resource(keyboard_input);
+    world.insert_resource(settings);
+    world.insert_resource(TrapChances {
+        disarm: 0.0,
+        set_off: 1.0,
+        ..default()
+    });
+    world.init_resource::<NextState<GameState>>();
+    world.init_resource::<Events<TrapTriggered>>();
+    world.spawn((Player, Position(markers[&'@'][0])));
+    let mut spawn_trap = |position: Vec2Int, detected: bool| {
+        world
+            .spawn((
+                Name::from(TrapKind::Pit.name()),
+                Position(position),
+                Trap {
+                    kind: TrapKind::Pit,
+                    detected,
+                },
+            ))
+            .id()
+    };
+    // Only the trap the player knows about is touched.
+    spawn_trap(markers[&'^'][0], false);
+    let known = spawn_trap(markers[&'^'][1], true);
+
+    world.run_system_once(disarm);
+    let triggered = world.resource::<Events<TrapTriggered>>();
+    let sent: Vec<Entity> = triggered
+        .get_reader()
+        .read(triggered)
+        .map(|event| event.trap)
+        .collect();
+    assert_eq!(sent, [known]);
+    assert_eq!(world.resource::<NextState<GameState>>().0, Some(GameState::EnemyTurn));
+
+    world.resource_mut::<TrapChances>().disarm = 1.0;
+    world.run_system_once(disarm);
+    assert!(world.get_entity(known).is_none());
+}
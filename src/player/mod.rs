@@ -1,16 +1,26 @@
 use bevy::prelude::*;
 
 use crate::{
+    animation::{animations_finished, AnimationQueue},
     common::{
-        components::Position, resources::CharsetAsset, states::GameState, Vec2Int, HEIGHT, WIDTH,
+        components::{Health, LightSource, Mobility, Position, RenderLayer, Renderable},
+        resources::InputFocus,
+        states::GameState,
     },
     map_generator::{Map, viewshed::Viewshed},
-    MainCamera,
 };
 
-use self::input::move_player;
+use self::{
+    activity::perform_activity,
+    input::{disarm, move_player, search, start_activity, throw, wait},
+    mouse::click_to_travel,
+};
+
+pub use self::input::{repeated_direction, InputSettings, KeyRepeat};
 
+mod activity;
 mod input;
+mod mouse;
 
 pub struct PlayerPlugin;
 
@@ -19,40 +29,50 @@ pub struct Player;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Setup), spawn_player)
-            .add_systems(PostUpdate, render_camera)
-            .add_systems(Update, move_player.run_if(in_state(GameState::PlayerTurn)));
+        app.init_resource::<InputSettings>()
+            .add_systems(OnEnter(GameState::Setup), spawn_player)
+            .add_systems(
+                Update,
+                (
+                    move_player,
+                    wait,
+                    search,
+                    disarm,
+                    throw,
+                    start_activity,
+                    click_to_travel,
+                    perform_activity,
+                )
+                    .run_if(
+                        in_state(GameState::PlayerTurn)
+                            .and_then(animations_finished)
+                            .and_then(resource_equals(InputFocus::Game)),
+                    ),
+            );
     }
 }
 
-fn spawn_player(map: Res<Map>, atlas: Res<CharsetAsset>, mut commands: Commands) {
-    let (x, y) = map.rooms.first().unwrap().center();
+fn spawn_player(map: Res<Map>, mut commands: Commands) {
+    let Some(start) = map.start() else {
+        error!("The map has no room to start in");
+        return;
+    };
     commands
-        .spawn(SpriteSheetBundle {
-            texture_atlas: atlas.atlas.clone(),
-            sprite: TextureAtlasSprite {
-                custom_size: Some(Vec2::new(1.0, 1.0)),
-                index: '@' as usize,
-                ..Default::default()
-            },
-            transform: Transform::from_scale(Vec3::new(WIDTH, HEIGHT, 1.0)),
-            ..Default::default()
+        .spawn(Renderable {
+            glyph: '@',
+            fg: Color::WHITE,
+            bg: None,
+            layer: RenderLayer::Player,
         })
         .insert(Player)
         .insert(Name::from("Player"))
-        .insert(Position(Vec2Int::new(x, y)))
-        .insert(Viewshed {range: 8.0});
-}
-
-fn render_camera(
-    players: Query<&Transform, (With<Player>, Without<MainCamera>)>,
-    mut cameras: Query<&mut Transform, With<MainCamera>>,
-) {
-    let Ok(player) = players.get_single() else {
-        return;
-    };
-
-    for mut camera in &mut cameras {
-        camera.translation = player.translation;
-    }
+        .insert(Position(start))
+        .insert(Viewshed::new(8.0))
+        .insert(Health { current: 30, max: 30 })
+        .insert(Mobility {
+            opens_doors: true,
+            digs: false,
+        })
+        .insert(LightSource { radius: 4.0 })
+        .insert(AnimationQueue::default());
 }
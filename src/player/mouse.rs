@@ -0,0 +1,113 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    common::{
+        components::{Mobility, Position},
+        pathfinding::Path,
+        resources::{HoveredTile, MovementRules},
+        Vec2Int,
+    },
+    map_generator::{viewshed::MapVisibility, Map},
+    trap::Trap,
+};
+
+use super::{activity::Activity, Player};
+
+/// Sends the player on their way to a remembered tile when it is clicked.
+pub fn click_to_travel(
+    mouse_input: Res<Input<MouseButton>>,
+    hovered: Res<HoveredTile>,
+    map: Res<Map>,
+    rules: Res<MovementRules>,
+    players: Query<(Entity, &Position, &Mobility), With<Player>>,
+    fog: Res<MapVisibility>,
+    traps: Query<(&Trap, &Position)>,
+    mut commands: Commands,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(target) = hovered.0 else {
+        return;
+    };
+    let known_traps: HashSet<_> = traps
+        .iter()
+        .filter(|(trap, _)| trap.detected)
+        .map(|(_, position)| position.0)
+        .collect();
+    for (entity, position, mobility) in &players {
+        let mobility = mobility.without_digging();
+        if let Some(path) = plan_travel(
+            position.0,
+            target,
+            &map,
+            &fog,
+            &rules,
+            mobility,
+            &known_traps,
+        ) {
+            commands.entity(entity).insert(Activity::Travel(path));
+        }
+    }
+}
+
+/// A path to `target` that only crosses tiles the player has seen, so travel never gives away
+/// or walks into what is still unexplored.
+fn plan_travel(
+    from: Vec2Int,
+    target: Vec2Int,
+    map: &Map,
+    fog: &MapVisibility,
+    rules: &MovementRules,
+    mobility: Mobility,
+    known_traps: &HashSet<Vec2Int>,
+) -> Option<Path> {
+    if !fog.is_revealed(map, target) {
+        return None;
+    }
+    Path::calculate_within(from, target, map, rules, mobility, known_traps, |tile| {
+        fog.is_revealed(map, tile)
+    })
+}
+
+#[test]
+fn test_plan_travel() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #######
+        #s#.#t#
+        #.#.#.#
+        #.....#
+        #######",
+    )
+    .unwrap();
+    let [start, target] = ['s', 't'].map(|marker| markers[&marker][0]);
+    let rules = MovementRules::default();
+    let mut fog = MapVisibility::new(&map);
+    fog.revealed.insert_range(..);
+    let path = plan_travel(
+        start,
+        target,
+        &map,
+        &fog,
+        &rules,
+        Mobility::default(),
+        &HashSet::new(),
+    );
+    assert!(path.is_some());
+
+    // The only way round goes through the unexplored bottom corridor.
+    for x in 2..5 {
+        fog.revealed.set(map.xy_idx(x, 1), false);
+    }
+    let path = plan_travel(
+        start,
+        target,
+        &map,
+        &fog,
+        &rules,
+        Mobility::default(),
+        &HashSet::new(),
+    );
+    assert_eq!(path, None);
+}
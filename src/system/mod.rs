@@ -1,9 +1,24 @@
 use bevy::prelude::*;
 
-use crate::common::components::Position;
+use crate::{
+    animation::AnimationQueue,
+    common::components::{Position, Renderable},
+};
 
-pub fn render(mut renderables: Query<(&mut Transform, &Position)>) {
-    for (mut transform, position) in &mut renderables {
-        transform.translation = position.to_world();
+// The terminal draws renderables itself and gets no sprites.
+#[cfg(not(feature = "terminal"))]
+pub use self::sprites::{spawn_sprites, update_sprites};
+
+#[cfg(not(feature = "terminal"))]
+mod sprites;
+
+pub fn render(
+    mut renderables: Query<
+        (Entity, &mut Transform, &Position, &Renderable),
+        Without<AnimationQueue>,
+    >,
+) {
+    for (entity, mut transform, position, renderable) in &mut renderables {
+        transform.translation = position.to_world(renderable.layer.z_of(entity));
     }
 }
@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+
+use crate::{
+    common::{components::Renderable, cp437, resources::CharsetAsset, HEIGHT, WIDTH},
+    map_generator::tilemap::FULL_BLOCK,
+};
+
+/// The full block behind a [`Renderable`] that paints its background color.
+#[derive(Component)]
+pub struct SpriteBackground;
+
+/// Turns every new [`Renderable`] into a sprite from the glyph atlas. Renderables with a
+/// background get it as a child just below the glyph.
+pub fn spawn_sprites(
+    atlas: Res<CharsetAsset>,
+    renderables: Query<(Entity, &Renderable, Has<Visibility>), Added<Renderable>>,
+    mut commands: Commands,
+) {
+    for (entity, renderable, has_visibility) in &renderables {
+        let mut sprite = commands.entity(entity);
+        sprite.insert((
+            atlas.atlas.clone(),
+            TextureAtlasSprite {
+                custom_size: Some(Vec2::new(1.0, 1.0)),
+                index: cp437::to_index(renderable.glyph),
+                color: renderable.fg,
+                ..Default::default()
+            },
+            TransformBundle::from_transform(Transform::from_scale(Vec3::new(WIDTH, HEIGHT, 1.0))),
+        ));
+        // Entities that start out hidden keep their visibility.
+        if !has_visibility {
+            sprite.insert(VisibilityBundle::default());
+        } else {
+            sprite.insert((InheritedVisibility::default(), ViewVisibility::default()));
+        }
+        if let Some(bg) = renderable.bg {
+            sprite.with_children(|parent| {
+                parent.spawn(background(&atlas, bg));
+            });
+        }
+    }
+}
+
+/// Keeps sprites in step with their [`Renderable`], adding or removing the background when it
+/// comes or goes.
+pub fn update_sprites(
+    atlas: Res<CharsetAsset>,
+    mut renderables: Query<
+        (
+            Entity,
+            &Renderable,
+            &mut TextureAtlasSprite,
+            Option<&Children>,
+        ),
+        Changed<Renderable>,
+    >,
+    mut backgrounds: Query<
+        (Entity, &mut TextureAtlasSprite),
+        (With<SpriteBackground>, Without<Renderable>),
+    >,
+    mut commands: Commands,
+) {
+    for (entity, renderable, mut sprite, children) in &mut renderables {
+        sprite.index = cp437::to_index(renderable.glyph);
+        sprite.color = renderable.fg;
+        let mut has_background = false;
+        if let Some(children) = children {
+            let mut children = backgrounds.iter_many_mut(children);
+            while let Some((child, mut background)) = children.fetch_next() {
+                match renderable.bg {
+                    Some(bg) => background.color = bg,
+                    None => commands.entity(child).despawn_recursive(),
+                }
+                has_background = true;
+            }
+        }
+        if let (Some(bg), false) = (renderable.bg, has_background) {
+            commands.entity(entity).with_children(|parent| {
+                parent.spawn(background(&atlas, bg));
+            });
+        }
+    }
+}
+
+fn background(atlas: &CharsetAsset, color: Color) -> (SpriteSheetBundle, SpriteBackground) {
+    (
+        SpriteSheetBundle {
+            texture_atlas: atlas.atlas.clone(),
+            sprite: TextureAtlasSprite {
+                custom_size: Some(Vec2::new(1.0, 1.0)),
+                index: FULL_BLOCK,
+                color,
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, -0.001),
+            ..Default::default()
+        },
+        SpriteBackground,
+    )
+}
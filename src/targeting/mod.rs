@@ -0,0 +1,387 @@
+use bevy::prelude::*;
+use bresenham::Bresenham;
+
+use crate::{
+    common::{
+        components::{Position, RenderLayer, Renderable},
+        resources::InputFocus,
+        Vec2Int,
+    },
+    enemy::Enemy,
+    item::Item,
+    map_generator::{viewshed::MapVisibility, Map},
+    player::{InputSettings, KeyRepeat, Player},
+    ui::{move_map_cursor, pause_menu, target_order},
+};
+
+pub struct TargetingPlugin;
+
+impl Plugin for TargetingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Targeting>()
+            .add_event::<StartTargeting>()
+            .add_event::<TargetChosen>()
+            .add_systems(
+                Update,
+                (start_targeting, aim, preview_line_of_fire)
+                    .chain()
+                    // Escape stops aiming before it can open the menu.
+                    .after(pause_menu)
+                    .run_if(resource_exists::<Map>()),
+            );
+    }
+}
+
+const PATH_COLOR: Color = Color::rgba(1.0, 1.0, 0.0, 0.2);
+const TARGET_COLOR: Color = Color::rgba(1.0, 1.0, 0.0, 0.35);
+const BLOCKED_COLOR: Color = Color::rgba(1.0, 0.0, 0.0, 0.35);
+
+/// Asks the player to pick a target. The cursor starts on the origin, movement keys move it and
+/// the next target key jumps between visible monsters and items. Only one target is picked at a
+/// time, requests while aiming are dropped.
+#[derive(Event, Clone, Copy)]
+pub struct StartTargeting {
+    /// Whoever the projectile starts from.
+    pub origin: Entity,
+    /// How far away a target may be, in tiles. Targets further away cannot be chosen.
+    pub range: Option<f32>,
+}
+
+/// Sent once a target is confirmed with enter, for whoever asked for it. Only targets in range
+/// can be confirmed, but the preview only knows what the player sees, so actions trace their
+/// own [`line_of_fire`].
+#[derive(Event)]
+pub struct TargetChosen {
+    pub origin: Entity,
+    pub tile: Vec2Int,
+    /// The monster or item on the tile, if one can be seen there.
+    pub entity: Option<Entity>,
+}
+
+/// The target being picked, if any.
+#[derive(Resource, Default)]
+pub struct Targeting {
+    pub request: Option<StartTargeting>,
+    pub cursor: Vec2Int,
+    /// From the origin to the cursor, updated whenever the cursor moves.
+    pub line: LineOfFire,
+}
+
+impl Targeting {
+    fn stop(&mut self, focus: &mut InputFocus) {
+        self.request = None;
+        *focus = InputFocus::Game;
+    }
+}
+
+/// The way a projectile would fly from one tile to another.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineOfFire {
+    /// The tiles it crosses, without the one it starts from. The last one is where it lands.
+    pub path: Vec<Vec2Int>,
+    /// Where a tile that blocks sight or a creature stops it before the target.
+    pub blocked_at: Option<Vec2Int>,
+    pub in_range: bool,
+}
+
+impl LineOfFire {
+    pub fn is_clear(&self) -> bool {
+        self.blocked_at.is_none() && self.in_range
+    }
+}
+
+/// Traces a projectile from `from` to `to` until a tile that blocks sight or a creature is in
+/// the way. The target itself never counts as being in the way.
+pub fn line_of_fire(
+    map: &Map,
+    from: Vec2Int,
+    to: Vec2Int,
+    range: Option<f32>,
+    is_creature: impl Fn(Vec2Int) -> bool,
+) -> LineOfFire {
+    let mut line = LineOfFire {
+        path: Vec::new(),
+        blocked_at: None,
+        in_range: range.is_none_or(|range| from.distance(&to) <= range),
+    };
+    // The line leaves out its end, the target is added after it.
+    let crossed = Bresenham::new(
+        (from.x as isize, from.y as isize),
+        (to.x as isize, to.y as isize),
+    )
+    .skip(1)
+    .map(|(x, y)| Vec2Int::new(x as i32, y as i32));
+    for tile in crossed {
+        line.path.push(tile);
+        if !map.in_bounds(tile) || map.blocks_sight(map.xy_idx(tile.x, tile.y)) || is_creature(tile)
+        {
+            line.blocked_at = Some(tile);
+            return line;
+        }
+    }
+    if from != to {
+        line.path.push(to);
+    }
+    line
+}
+
+/// Marks the target and the line of fire on the map.
+#[derive(Component)]
+pub struct TargetMarker;
+
+fn start_targeting(
+    mut requests: EventReader<StartTargeting>,
+    mut focus: ResMut<InputFocus>,
+    mut targeting: ResMut<Targeting>,
+    positions: Query<&Position>,
+) {
+    for request in requests.read() {
+        if *focus != InputFocus::Game {
+            continue;
+        }
+        let Ok(origin) = positions.get(request.origin) else {
+            continue;
+        };
+        *focus = InputFocus::Target;
+        targeting.request = Some(*request);
+        targeting.cursor = origin.0;
+    }
+}
+
+fn aim(
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    settings: Res<InputSettings>,
+    mut repeat: Local<KeyRepeat>,
+    mut focus: ResMut<InputFocus>,
+    mut targeting: ResMut<Targeting>,
+    map: Res<Map>,
+    fog: Res<MapVisibility>,
+    positions: Query<&Position>,
+    targets: Query<(Entity, &Position, &Visibility, Has<Enemy>), Or<(With<Enemy>, With<Item>)>>,
+    mut chosen: EventWriter<TargetChosen>,
+) {
+    let Some(request) = targeting.request else {
+        return;
+    };
+    if *focus != InputFocus::Target {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        targeting.stop(&mut focus);
+        return;
+    }
+    let Ok(origin) = positions.get(request.origin).map(|position| position.0) else {
+        targeting.stop(&mut focus);
+        return;
+    };
+    let in_range = |tile: Vec2Int| {
+        request
+            .range
+            .is_none_or(|range| origin.distance(&tile) <= range)
+    };
+    let visible = targets.iter().filter(|(_, position, shown, _)| {
+        **shown != Visibility::Hidden && fog.is_visible(&map, position.0)
+    });
+
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        if !targeting.line.in_range {
+            return;
+        }
+        let tile = targeting.cursor;
+        // Monsters before items when both share the tile.
+        let entity = visible
+            .filter(|(_, position, _, _)| position.0 == tile)
+            .max_by_key(|(_, _, _, enemy)| *enemy)
+            .map(|(entity, _, _, _)| entity);
+        chosen.send(TargetChosen {
+            origin: request.origin,
+            tile,
+            entity,
+        });
+        targeting.stop(&mut focus);
+        return;
+    }
+    let cursor = targeting.cursor;
+    let next = move_map_cursor(
+        cursor,
+        &keyboard_input,
+        &time,
+        &settings,
+        &mut repeat,
+        &map,
+        || {
+            target_order(
+                origin,
+                visible
+                    .filter(|(_, position, _, _)| in_range(position.0))
+                    .map(|(_, position, _, enemy)| (position.0, enemy)),
+            )
+        },
+    );
+    if let Some(next) = next {
+        targeting.cursor = next;
+    }
+}
+
+/// Traces the line of fire again and redraws it whenever aiming starts, stops or moves.
+fn preview_line_of_fire(
+    mut targeting: ResMut<Targeting>,
+    map: Res<Map>,
+    fog: Res<MapVisibility>,
+    positions: Query<&Position>,
+    creatures: Query<(&Position, &Visibility), Or<(With<Enemy>, With<Player>)>>,
+    markers: Query<Entity, With<TargetMarker>>,
+    mut commands: Commands,
+) {
+    if !targeting.is_changed() {
+        return;
+    }
+    for marker in &markers {
+        commands.entity(marker).despawn_recursive();
+    }
+    let Some(request) = targeting.request else {
+        return;
+    };
+    let Ok(origin) = positions.get(request.origin) else {
+        return;
+    };
+    // Only creatures the player can see are in the way, the preview must not give away the
+    // rest.
+    let is_creature = |tile: Vec2Int| {
+        creatures.iter().any(|(position, shown)| {
+            position.0 == tile && *shown != Visibility::Hidden && fog.is_visible(&map, tile)
+        })
+    };
+    let cursor = targeting.cursor;
+    let line = line_of_fire(&map, origin.0, cursor, request.range, is_creature);
+
+    let mut mark = |tile: Vec2Int, color: Color| {
+        commands
+            .spawn(Renderable {
+                glyph: ' ',
+                fg: Color::NONE,
+                bg: Some(color),
+                layer: RenderLayer::Overlay,
+            })
+            .insert(Name::from("Target Marker"))
+            .insert(TargetMarker)
+            .insert(Position(tile));
+    };
+    for &tile in &line.path {
+        if tile != cursor && fog.is_revealed(&map, tile) {
+            let color = if Some(tile) == line.blocked_at {
+                BLOCKED_COLOR
+            } else {
+                PATH_COLOR
+            };
+            mark(tile, color);
+        }
+    }
+    mark(
+        cursor,
+        if line.is_clear() {
+            TARGET_COLOR
+        } else {
+            BLOCKED_COLOR
+        },
+    );
+    // The line only depends on the cursor, tracing it must not count as another move.
+    targeting.bypass_change_detection().line = line;
+}
+
+#[test]
+fn test_line_of_fire() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #########
+        #@..g.#x#
+        #########",
+    )
+    .unwrap();
+    let player = markers[&'@'][0];
+    let goblin = markers[&'g'][0];
+    let beyond = goblin + Vec2Int::RIGHT;
+
+    let line = line_of_fire(&map, player, goblin, Some(5.0), |_| false);
+    assert_eq!(line.path.last(), Some(&goblin));
+    assert_eq!(line.path.len(), 3);
+    assert!(line.is_clear());
+
+    let line = line_of_fire(&map, player, beyond, Some(3.0), |tile| tile == goblin);
+    assert_eq!(line.blocked_at, Some(goblin));
+    assert_eq!(line.path.last(), Some(&goblin));
+    assert!(!line.in_range);
+
+    let line = line_of_fire(&map, player, markers[&'x'][0], None, |_| false);
+    assert_eq!(line.blocked_at, Some(Vec2Int::new(6, 1)));
+}
+
+#[test]
+fn test_aim() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #######
+        #@....#
+        #######",
+    )
+    .unwrap();
+    let start = markers[&'@'][0];
+    let mut world = World::new();
+    world.insert_resource(MapVisibility::new(&map));
+    world.insert_resource(map);
+    world.insert_resource(InputSettings::default());
+    world.init_resource::<Input<KeyCode>>();
+    world.init_resource::<Time>();
+    world.init_resource::<InputFocus>();
+    world.init_resource::<Targeting>();
+    world.init_resource::<Events<StartTargeting>>();
+    world.init_resource::<Events<TargetChosen>>();
+    let player = world.spawn((Player, Position(start))).id();
+    let mut schedule = Schedule::default();
+    schedule.add_systems((start_targeting, aim, preview_line_of_fire).chain());
+    let press = |world: &mut World, schedule: &mut Schedule, key: KeyCode| {
+        world.resource_mut::<Input<KeyCode>>().press(key);
+        schedule.run(world);
+        let mut keyboard_input = world.resource_mut::<Input<KeyCode>>();
+        keyboard_input.release(key);
+        keyboard_input.clear();
+    };
+    let chosen = |world: &World| {
+        let events = world.resource::<Events<TargetChosen>>();
+        let tiles: Vec<Vec2Int> = events
+            .get_reader()
+            .read(events)
+            .map(|event| event.tile)
+            .collect();
+        tiles
+    };
+
+    world.send_event(StartTargeting {
+        origin: player,
+        range: Some(2.0),
+    });
+    schedule.run(&mut world);
+    assert_eq!(*world.resource::<InputFocus>(), InputFocus::Target);
+    for _ in 0..3 {
+        press(&mut world, &mut schedule, KeyCode::D);
+    }
+    // Out of range, the cursor stays up.
+    press(&mut world, &mut schedule, KeyCode::Return);
+    assert!(chosen(&world).is_empty());
+    assert_eq!(*world.resource::<InputFocus>(), InputFocus::Target);
+    press(&mut world, &mut schedule, KeyCode::A);
+    press(&mut world, &mut schedule, KeyCode::Return);
+    assert_eq!(chosen(&world), [start + Vec2Int::new(2, 0)]);
+    assert_eq!(*world.resource::<InputFocus>(), InputFocus::Game);
+
+    world.send_event(StartTargeting {
+        origin: player,
+        range: None,
+    });
+    schedule.run(&mut world);
+    press(&mut world, &mut schedule, KeyCode::Escape);
+    assert_eq!(*world.resource::<InputFocus>(), InputFocus::Game);
+    assert!(world.resource::<Targeting>().request.is_none());
+    assert_eq!(chosen(&world).len(), 1);
+}
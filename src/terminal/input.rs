@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use bevy::{app::AppExit, prelude::*};
+use crossterm::event::{self, Event, KeyCode as TerminalKey, KeyEventKind, KeyModifiers};
+
+const LETTERS: [KeyCode; 26] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+];
+
+// Terminals can't tell the number row from the keypad, so digits count as keypad keys.
+const DIGITS: [KeyCode; 10] = [
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+];
+
+fn to_key_code(key: TerminalKey) -> Option<KeyCode> {
+    match key {
+        TerminalKey::Char(' ') => Some(KeyCode::Space),
+        TerminalKey::Char(c) if c.is_ascii_alphabetic() => {
+            Some(LETTERS[(c.to_ascii_lowercase() as u8 - b'a') as usize])
+        }
+        TerminalKey::Char(c) if c.is_ascii_digit() => Some(DIGITS[(c as u8 - b'0') as usize]),
+        TerminalKey::Esc => Some(KeyCode::Escape),
+        TerminalKey::Enter => Some(KeyCode::Return),
+        TerminalKey::Tab => Some(KeyCode::Tab),
+        TerminalKey::Up => Some(KeyCode::Up),
+        TerminalKey::Down => Some(KeyCode::Down),
+        TerminalKey::Backspace => Some(KeyCode::Back),
+        _ => None,
+    }
+}
+
+/// Feeds terminal key presses into bevy's keyboard input. Terminals only report presses,
+/// so every key is released again on the following frame.
+pub fn read_terminal_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut pressed: Local<Vec<KeyCode>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for key in pressed.drain(..) {
+        keyboard_input.release(key);
+    }
+
+    while event::poll(Duration::ZERO).unwrap_or(false) {
+        let Ok(Event::Key(key)) = event::read() else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        // Raw mode swallows the interrupt signal, so Ctrl+C has to quit by hand.
+        if key.code == TerminalKey::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            exit.send(AppExit);
+            continue;
+        }
+        let Some(code) = to_key_code(key.code) else {
+            continue;
+        };
+        let shifted = matches!(key.code, TerminalKey::Char(c) if c.is_ascii_uppercase());
+        if shifted || key.modifiers.contains(KeyModifiers::SHIFT) {
+            keyboard_input.press(KeyCode::ShiftLeft);
+            pressed.push(KeyCode::ShiftLeft);
+        }
+        keyboard_input.press(code);
+        pressed.push(code);
+    }
+}
@@ -0,0 +1,71 @@
+use std::{io::stdout, time::Duration};
+
+use bevy::{
+    app::{AppExit, PluginGroupBuilder, ScheduleRunnerPlugin},
+    input::InputSystem,
+    prelude::*,
+    render::{settings::WgpuSettings, RenderPlugin},
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use crossterm::{cursor, execute, terminal};
+
+use self::{input::read_terminal_input, render::draw_terminal};
+
+mod input;
+mod render;
+
+const FRAME_TIME: Duration = Duration::from_millis(33);
+
+/// Bevy's default plugins without a window or a GPU, ticking on a fixed loop instead.
+pub fn headless_plugins() -> PluginGroupBuilder {
+    DefaultPlugins
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+        })
+        .set(RenderPlugin {
+            render_creation: WgpuSettings {
+                backends: None,
+                ..default()
+            }
+            .into(),
+        })
+        .disable::<WinitPlugin>()
+        .add(ScheduleRunnerPlugin::run_loop(FRAME_TIME))
+}
+
+/// Draws the game to the terminal it was started from and reads the keyboard from it.
+pub struct TerminalPlugin;
+
+impl Plugin for TerminalPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, enter_terminal)
+            .add_systems(PreUpdate, read_terminal_input.after(InputSystem))
+            .add_systems(PostUpdate, draw_terminal)
+            .add_systems(Last, leave_terminal.run_if(on_event::<AppExit>()));
+    }
+}
+
+fn enter_terminal() {
+    terminal::enable_raw_mode().expect("Failed to switch the terminal to raw mode");
+    execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)
+        .expect("Failed to prepare the terminal");
+
+    // Don't leave the terminal in raw mode when the game goes down.
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        hook(info);
+    }));
+}
+
+fn leave_terminal() {
+    restore_terminal();
+}
+
+fn restore_terminal() {
+    let _ = execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
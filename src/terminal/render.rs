@@ -0,0 +1,168 @@
+use std::io::{stdout, Write};
+
+use bevy::prelude::*;
+use crossterm::{cursor, queue, style, terminal};
+
+use crate::{
+    common::{
+        components::{Position, Renderable},
+        Vec2Int,
+    },
+    map_generator::{lighting::LightMap, tilemap::TileAppearance, viewshed::MapVisibility, Map},
+    player::Player,
+    ui::console::Console,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    glyph: char,
+    color: [u8; 3],
+    background: [u8; 3],
+}
+
+impl Cell {
+    const EMPTY: Self = Self {
+        glyph: ' ',
+        color: [0, 0, 0],
+        background: [0, 0, 0],
+    };
+
+    fn paint(&mut self, appearance: &TileAppearance) {
+        let [r, g, b, _] = appearance.fg.as_rgba_u8();
+        let [bg_r, bg_g, bg_b, _] = appearance.bg.as_rgba_u8();
+        self.glyph = appearance.glyph;
+        self.color = [r, g, b];
+        self.background = [bg_r, bg_g, bg_b];
+    }
+
+    /// Draws the sprite's glyph over whatever background the cell has.
+    fn draw(&mut self, glyph: char, fg: Color, bg: Option<Color>) {
+        let [r, g, b, _] = fg.as_rgba_u8();
+        self.glyph = glyph;
+        self.color = [r, g, b];
+        if let Some(bg) = bg {
+            let [r, g, b, _] = bg.as_rgba_u8();
+            self.background = [r, g, b];
+        }
+    }
+}
+
+/// What was drawn last frame, so only changed cells have to be sent to the terminal.
+#[derive(Default)]
+pub struct Screen {
+    cells: Vec<Cell>,
+    size: (u16, u16),
+}
+
+/// Mirrors the sprite renderer: tiles look the way the tilemap chunks draw them, renderables
+/// other than the player are only shown while their tile is in view and consoles go on top.
+pub fn draw_terminal(
+    map: Option<Res<Map>>,
+    fog: Option<Res<MapVisibility>>,
+    light: Res<LightMap>,
+    renderables: Query<(&Position, &Renderable, Option<&Visibility>, Has<Player>)>,
+    consoles: Query<(&Console, &Visibility)>,
+    mut screen: Local<Screen>,
+) {
+    let (Some(map), Some(fog)) = (map, fog) else {
+        return;
+    };
+    let Ok((columns, rows)) = terminal::size() else {
+        return;
+    };
+
+    let center = renderables
+        .iter()
+        .find(|(_, _, _, is_player)| *is_player)
+        .map(|(position, _, _, _)| position.0)
+        .unwrap_or(Vec2Int::new(map.width / 2, map.height / 2));
+    // Terminal rows grow downwards while map rows grow upwards.
+    let left = center.x - columns as i32 / 2;
+    let top = center.y + rows as i32 / 2;
+    let to_screen = |position: Vec2Int| {
+        let column = position.x - left;
+        let row = top - position.y;
+        if column < 0 || row < 0 || column >= columns as i32 || row >= rows as i32 {
+            return None;
+        }
+        Some(row as usize * columns as usize + column as usize)
+    };
+
+    let mut cells = vec![Cell::EMPTY; columns as usize * rows as usize];
+    for (index, cell) in cells.iter_mut().enumerate() {
+        let position = Vec2Int::new(
+            left + (index % columns as usize) as i32,
+            top - (index / columns as usize) as i32,
+        );
+        let revealed = fog.is_revealed(&map, position);
+        let in_view = fog.is_visible(&map, position);
+        if let Some(appearance) = TileAppearance::of(&map, &light, position, revealed, in_view) {
+            cell.paint(&appearance);
+        }
+    }
+    let mut shown: Vec<_> = renderables
+        .iter()
+        .filter(|(position, _, visibility, is_player)| {
+            *visibility != Some(&Visibility::Hidden)
+                && (*is_player || fog.is_visible(&map, position.0))
+        })
+        .collect();
+    shown.sort_by_key(|(_, renderable, ..)| renderable.layer);
+    for (position, renderable, ..) in shown {
+        if let Some(cell) = to_screen(position.0) {
+            cells[cell].draw(renderable.glyph, renderable.fg, renderable.bg);
+        }
+    }
+    for (console, visibility) in &consoles {
+        if visibility == Visibility::Hidden {
+            continue;
+        }
+        let (left, top) = console.origin(columns as i32, rows as i32);
+        for y in 0..console.height {
+            for x in 0..console.width {
+                let (column, row) = (left + x, top + y);
+                if column < 0 || row < 0 || column >= columns as i32 || row >= rows as i32 {
+                    continue;
+                }
+                let Some(cell) = console.cell(x, y) else {
+                    continue;
+                };
+                if cell.glyph != ' ' || cell.bg.is_some() {
+                    cells[row as usize * columns as usize + column as usize]
+                        .draw(cell.glyph, cell.fg, cell.bg);
+                }
+            }
+        }
+    }
+
+    let mut out = stdout().lock();
+    let resized = screen.size != (columns, rows);
+    if resized {
+        let _ = queue!(out, terminal::Clear(terminal::ClearType::All));
+    }
+    for (index, cell) in cells.iter().enumerate() {
+        if !resized && screen.cells.get(index) == Some(cell) {
+            continue;
+        }
+        let [r, g, b] = cell.color;
+        let [bg_r, bg_g, bg_b] = cell.background;
+        let _ = queue!(
+            out,
+            cursor::MoveTo(
+                (index % columns as usize) as u16,
+                (index / columns as usize) as u16
+            ),
+            style::SetForegroundColor(style::Color::Rgb { r, g, b }),
+            style::SetBackgroundColor(style::Color::Rgb {
+                r: bg_r,
+                g: bg_g,
+                b: bg_b
+            }),
+            style::Print(cell.glyph)
+        );
+    }
+    let _ = out.flush();
+
+    screen.cells = cells;
+    screen.size = (columns, rows);
+}
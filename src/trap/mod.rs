@@ -0,0 +1,427 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use rand::Rng;
+
+use crate::{
+    common::{
+        components::{Health, Mobility, Position, RenderLayer, Renderable},
+        pathfinding::Path,
+        resources::MovementRules,
+        spawns::{Monster, Spawn, TrapKind},
+        states::GameState,
+        Vec2Int,
+    },
+    enemy::{spawn_monster, Enemy, MonsterIds},
+    map_generator::{viewshed::Viewshed, Map},
+    player::Player,
+};
+
+pub struct TrapPlugin;
+
+impl Plugin for TrapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrapChances>()
+            .add_event::<TrapTriggered>()
+            .add_systems(OnEnter(GameState::Setup), spawn_traps)
+            .add_systems(OnEnter(GameState::EnemyTurn), notice_traps)
+            .add_systems(Update, (remember_traps, reveal_traps))
+            .add_systems(PostUpdate, (step_on_traps, spring_traps).chain());
+    }
+}
+
+/// How many goblins a summoning trap calls.
+const SUMMONED: usize = 2;
+
+/// The odds of finding, noticing and disarming traps.
+#[derive(Resource)]
+pub struct TrapChances {
+    /// Chance to find a hidden trap next to the player when searching for one.
+    pub search: f64,
+    /// Chance that an attempt to disarm a known trap succeeds.
+    pub disarm: f64,
+    /// Chance that a failed disarm attempt sets the trap off.
+    pub set_off: f64,
+    /// Chances to notice a hidden trap in view each turn without searching, next to the player
+    /// and further away.
+    pub notice_near: f64,
+    pub notice_far: f64,
+}
+
+impl Default for TrapChances {
+    fn default() -> Self {
+        Self {
+            search: 0.75,
+            disarm: 0.6,
+            set_off: 1.0 / 3.0,
+            notice_near: 0.25,
+            notice_far: 0.05,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Trap {
+    pub kind: TrapKind,
+    /// Hidden traps are invisible to the player until they are found or set off.
+    pub detected: bool,
+}
+
+impl Trap {
+    /// Whether the player has yet to find the trap. Nothing may name or show a hidden trap.
+    pub fn hidden(&self) -> bool {
+        !self.detected
+    }
+}
+
+/// The traps a monster has seen, it keeps away from them.
+#[derive(Component, Default)]
+pub struct TrapMemory(pub HashSet<Vec2Int>);
+
+#[derive(Event)]
+pub struct TrapTriggered {
+    pub trap: Entity,
+    pub victim: Entity,
+}
+
+fn spawn_traps(map: Res<Map>, mut commands: Commands) {
+    for (position, spawn) in &map.spawns {
+        let Spawn::Trap(kind) = spawn else {
+            continue;
+        };
+        commands
+            .spawn(Renderable {
+                glyph: '^',
+                fg: kind.color(),
+                bg: None,
+                layer: RenderLayer::Decals,
+            })
+            .insert(Visibility::Hidden)
+            .insert(Name::from(kind.name()))
+            .insert(Position(*position))
+            .insert(Trap {
+                kind: *kind,
+                detected: false,
+            });
+    }
+}
+
+fn reveal_traps(mut traps: Query<(&Trap, &mut Visibility), Changed<Trap>>) {
+    for (trap, mut visibility) in &mut traps {
+        if trap.detected {
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+/// Passive perception, rolled once for every hidden trap in view after each player turn.
+fn notice_traps(
+    chances: Res<TrapChances>,
+    players: Query<(&Position, &Viewshed), With<Player>>,
+    mut traps: Query<(&mut Trap, &Position, &Name), Without<Player>>,
+) {
+    let Ok((player, viewshed)) = players.get_single() else {
+        return;
+    };
+    let mut rng = rand::thread_rng();
+    for (mut trap, position, name) in &mut traps {
+        if trap.detected || !viewshed.visible.contains(&position.0) {
+            continue;
+        }
+        let chance = if player.0.distance(&position.0) < 1.5 {
+            chances.notice_near
+        } else {
+            chances.notice_far
+        };
+        if rng.gen_bool(chance) {
+            trap.detected = true;
+            info!("You notice a {}", name.to_lowercase());
+        }
+    }
+}
+
+/// Monsters know every trap they have laid eyes on, hidden or not.
+fn remember_traps(
+    mut monsters: Query<(&Viewshed, &mut TrapMemory), Changed<Viewshed>>,
+    traps: Query<&Position, With<Trap>>,
+) {
+    for (viewshed, mut memory) in &mut monsters {
+        for position in &traps {
+            if viewshed.visible.contains(&position.0) {
+                memory.0.insert(position.0);
+            }
+        }
+    }
+}
+
+/// Sets off the traps that the player or a monster stepped onto since the last frame.
+fn step_on_traps(
+    mut last_positions: Local<HashMap<Entity, Vec2Int>>,
+    walkers: Query<(Entity, &Position), (Changed<Position>, Or<(With<Player>, With<Enemy>)>)>,
+    traps: Query<(Entity, &Position), With<Trap>>,
+    mut triggered: EventWriter<TrapTriggered>,
+) {
+    for (victim, position) in &walkers {
+        // Opening doors or digging also touches the position without moving, and freshly
+        // spawned walkers did not step anywhere.
+        let Some(last) = last_positions.insert(victim, position.0) else {
+            continue;
+        };
+        if last == position.0 {
+            continue;
+        }
+        for (trap, trap_position) in &traps {
+            if trap_position.0 == position.0 {
+                triggered.send(TrapTriggered { trap, victim });
+            }
+        }
+    }
+}
+
+fn spring_traps(
+    mut triggered: EventReader<TrapTriggered>,
+    map: Res<Map>,
+    rules: Res<MovementRules>,
+    mut traps: Query<(&mut Trap, &Position, &Name)>,
+    mut victims: Query<(&mut Position, Option<&mut Health>, &Name, Has<Player>), Without<Trap>>,
+    viewers: Query<&Viewshed, With<Player>>,
+    enemies: Query<(Entity, &Mobility, &TrapMemory), With<Enemy>>,
+    mut monster_ids: ResMut<MonsterIds>,
+    mut commands: Commands,
+) {
+    let mut rng = rand::thread_rng();
+    for event in triggered.read() {
+        let Ok((mut trap, trap_position, trap_name)) = traps.get_mut(event.trap) else {
+            continue;
+        };
+        let Ok((position, health, name, is_player)) = victims.get_mut(event.victim) else {
+            continue;
+        };
+        info!("{} sets off a {}", name, trap_name.to_lowercase());
+        if is_player
+            || viewers
+                .iter()
+                .any(|viewshed| viewshed.visible.contains(&trap_position.0))
+        {
+            trap.detected = true;
+        }
+
+        match trap.kind {
+            TrapKind::Pit | TrapKind::Dart => {
+                let damage = if trap.kind == TrapKind::Pit {
+                    rng.gen_range(2..=5)
+                } else {
+                    rng.gen_range(1..=4)
+                };
+                if let Some(mut health) = health {
+                    health.current -= damage;
+                    info!("{} takes {} damage", name, damage);
+                }
+            }
+            TrapKind::Teleport => {
+                let taken: HashSet<Vec2Int> = traps
+                    .iter()
+                    .map(|(_, position, _)| position.0)
+                    .chain(victims.iter().map(|(position, ..)| position.0))
+                    .collect();
+                let destinations: Vec<Vec2Int> = (0..map.len())
+                    .filter(|idx| !map.is_occupied(*idx))
+                    .map(|idx| {
+                        let (x, y) = map.idx_xy(idx);
+                        Vec2Int::new(x, y)
+                    })
+                    .filter(|destination| !taken.contains(destination))
+                    .collect();
+                if !destinations.is_empty() {
+                    let destination = destinations[rng.gen_range(0..destinations.len())];
+                    if let Ok((mut position, ..)) = victims.get_mut(event.victim) {
+                        position.0 = destination;
+                    }
+                }
+            }
+            TrapKind::Alarm => {
+                let target = position.0;
+                for (enemy, mobility, memory) in &enemies {
+                    let Ok((enemy_position, ..)) = victims.get(enemy) else {
+                        continue;
+                    };
+                    let path = Path::calculate(
+                        enemy_position.0,
+                        target,
+                        &map,
+                        &rules,
+                        *mobility,
+                        &memory.0,
+                    );
+                    if let Some(path) = path {
+                        commands.entity(enemy).insert(path);
+                    }
+                }
+            }
+            TrapKind::Summon => {
+                let taken: HashSet<Vec2Int> =
+                    victims.iter().map(|(position, ..)| position.0).collect();
+                let free = Vec2Int::DIRECTIONS
+                    .iter()
+                    .map(|direction| trap_position.0 + *direction)
+                    .filter(|tile| {
+                        map.in_bounds(*tile)
+                            && !map.is_occupied(map.xy_idx(tile.x, tile.y))
+                            && !taken.contains(tile)
+                    });
+                for tile in free.take(SUMMONED) {
+                    spawn_monster(&mut commands, Monster::Goblin, tile, monster_ids.next());
+                }
+                // The summoning circle burns out after calling once.
+                commands.entity(event.trap).despawn();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_spring_trap() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #####
+        #@^.#
+        #####",
+    )
+    .unwrap();
+    let trap_position = markers[&'^'][0];
+    let mut world = World::new();
+    world.insert_resource(map);
+    world.init_resource::<MovementRules>();
+    world.init_resource::<MonsterIds>();
+    world.init_resource::<Events<TrapTriggered>>();
+    let player = world
+        .spawn((
+            Player,
+            Name::from("Player"),
+            Position(markers[&'@'][0]),
+            Health {
+                current: 10,
+                max: 10,
+            },
+        ))
+        .id();
+    let trap = world
+        .spawn((
+            Name::from(TrapKind::Pit.name()),
+            Position(trap_position),
+            Trap {
+                kind: TrapKind::Pit,
+                detected: false,
+            },
+        ))
+        .id();
+    let mut schedule = Schedule::default();
+    schedule.add_systems((step_on_traps, spring_traps).chain());
+
+    // Standing still next to the trap sets nothing off.
+    schedule.run(&mut world);
+    assert!(world.get::<Trap>(trap).unwrap().hidden());
+    world.get_mut::<Position>(player).unwrap().0 = trap_position;
+    schedule.run(&mut world);
+    assert!(!world.get::<Trap>(trap).unwrap().hidden());
+    assert!((5..=8).contains(&world.get::<Health>(player).unwrap().current));
+}
+
+#[test]
+fn test_notice_traps() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let (map, markers) = Map::from_ascii(
+        "
+        ########
+        #@^..^.#
+        ########",
+    )
+    .unwrap();
+    let (near, far) = (markers[&'^'][0], markers[&'^'][1]);
+    let mut world = World::new();
+    world.insert_resource(map);
+    world.insert_resource(TrapChances {
+        notice_near: 1.0,
+        notice_far: 0.0,
+        ..default()
+    });
+    let mut viewshed = Viewshed::new(8.0);
+    viewshed.visible.extend([near, far]);
+    world.spawn((Player, Position(markers[&'@'][0]), viewshed));
+    let traps = [near, far].map(|position| {
+        world
+            .spawn((
+                Name::from(TrapKind::Dart.name()),
+                Position(position),
+                Visibility::Hidden,
+                Trap {
+                    kind: TrapKind::Dart,
+                    detected: false,
+                },
+            ))
+            .id()
+    });
+
+    world.run_system_once(notice_traps);
+    world.run_system_once(reveal_traps);
+    assert!(!world.get::<Trap>(traps[0]).unwrap().hidden());
+    assert_eq!(
+        world.get::<Visibility>(traps[0]),
+        Some(&Visibility::Inherited)
+    );
+    assert!(world.get::<Trap>(traps[1]).unwrap().hidden());
+    assert_eq!(world.get::<Visibility>(traps[1]), Some(&Visibility::Hidden));
+}
+
+#[test]
+fn test_alarm_trap() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let (map, markers) = Map::from_ascii(
+        "
+        ########
+        #^....g#
+        ########",
+    )
+    .unwrap();
+    let alarm = markers[&'^'][0];
+    let mut world = World::new();
+    world.insert_resource(map);
+    world.init_resource::<MovementRules>();
+    world.init_resource::<MonsterIds>();
+    world.init_resource::<Events<TrapTriggered>>();
+    let player = world
+        .spawn((Player, Name::from("Player"), Position(alarm)))
+        .id();
+    let goblin = world
+        .spawn((
+            Enemy,
+            Name::from("Goblin"),
+            Position(markers[&'g'][0]),
+            Mobility::default(),
+            TrapMemory::default(),
+        ))
+        .id();
+    let trap = world
+        .spawn((
+            Name::from(TrapKind::Alarm.name()),
+            Position(alarm),
+            Trap {
+                kind: TrapKind::Alarm,
+                detected: false,
+            },
+        ))
+        .id();
+
+    world.send_event(TrapTriggered {
+        trap,
+        victim: player,
+    });
+    world.run_system_once(spring_traps);
+    let path = world
+        .get::<Path>(goblin)
+        .expect("the goblin heads for the alarm");
+    assert_eq!(path.waypoints.back(), Some(&alarm));
+}
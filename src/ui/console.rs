@@ -0,0 +1,311 @@
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::*,
+    render::view::RenderLayers,
+    sprite::{Anchor, Mesh2dHandle},
+    window::PrimaryWindow,
+};
+
+use crate::{
+    common::{components::RenderLayer, cp437, resources::CharsetAsset},
+    map_generator::tilemap::{GlyphMesh, FULL_BLOCK},
+};
+
+/// Size of a console cell on screen, in pixels.
+pub const CELL_SIZE: f32 = 16.0;
+/// Consoles are drawn by their own camera on top of the map, whatever the map camera does.
+const CONSOLE_LAYER: RenderLayers = RenderLayers::layer(1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConsoleCell {
+    pub glyph: char,
+    pub fg: Color,
+    /// Cells without a background let the map show through.
+    pub bg: Option<Color>,
+}
+
+impl Default for ConsoleCell {
+    fn default() -> Self {
+        Self {
+            glyph: ' ',
+            fg: Color::WHITE,
+            bg: None,
+        }
+    }
+}
+
+/// A grid of character cells on screen, for menus, HUDs and logs. Columns run to the right and
+/// rows downwards from the top left cell, anything printed outside the grid is cut off.
+#[derive(Component)]
+pub struct Console {
+    pub width: i32,
+    pub height: i32,
+    /// The point of the screen the console sticks to, the same point of the console is put
+    /// there.
+    pub anchor: Anchor,
+    cells: Vec<ConsoleCell>,
+}
+
+impl Console {
+    pub fn new(width: i32, height: i32, anchor: Anchor) -> Self {
+        Self {
+            width,
+            height,
+            anchor,
+            cells: vec![ConsoleCell::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn cell(&self, x: i32, y: i32) -> Option<&ConsoleCell> {
+        self.index(x, y).map(|index| &self.cells[index])
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let inside = (0..self.width).contains(&x) && (0..self.height).contains(&y);
+        inside.then(|| (y * self.width + x) as usize)
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, glyph: char, fg: Color, bg: Option<Color>) {
+        if let Some(index) = self.index(x, y) {
+            self.cells[index] = ConsoleCell { glyph, fg, bg };
+        }
+    }
+
+    /// Writes `text` from left to right, keeping the backgrounds it is written over.
+    pub fn print(&mut self, x: i32, y: i32, text: &str, fg: Color) {
+        for (offset, glyph) in text.chars().enumerate() {
+            if let Some(index) = self.index(x + offset as i32, y) {
+                let cell = &mut self.cells[index];
+                cell.glyph = glyph;
+                cell.fg = fg;
+            }
+        }
+    }
+
+    /// Blanks a rectangle of cells and gives it a background.
+    pub fn fill(&mut self, x: i32, y: i32, width: i32, height: i32, bg: Option<Color>) {
+        for row in y..y + height {
+            for column in x..x + width {
+                self.set(column, row, ' ', Color::WHITE, bg);
+            }
+        }
+    }
+
+    /// Frames a rectangle with single lines and blanks everything inside.
+    pub fn draw_box(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        fg: Color,
+        bg: Option<Color>,
+    ) {
+        self.fill(x, y, width, height, bg);
+        let [right, bottom] = [x + width - 1, y + height - 1];
+        for column in x + 1..right {
+            self.set(column, y, '─', fg, bg);
+            self.set(column, bottom, '─', fg, bg);
+        }
+        for row in y + 1..bottom {
+            self.set(x, row, '│', fg, bg);
+            self.set(right, row, '│', fg, bg);
+        }
+        self.set(x, y, '┌', fg, bg);
+        self.set(right, y, '┐', fg, bg);
+        self.set(x, bottom, '└', fg, bg);
+        self.set(right, bottom, '┘', fg, bg);
+    }
+
+    /// Prints the entries one per row, the selected one marked and highlighted.
+    pub fn list(&mut self, x: i32, y: i32, list: &SelectList, fg: Color, highlight: Color) {
+        for (row, item) in list.items.iter().enumerate() {
+            let row = y + row as i32;
+            if row == y + list.selected as i32 {
+                self.print(x, row, "►", highlight);
+                self.print(x + 2, row, item, highlight);
+            } else {
+                self.print(x + 2, row, item, fg);
+            }
+        }
+    }
+
+    /// Top left cell of the console on a screen of `columns` by `rows` cells.
+    pub fn origin(&self, columns: i32, rows: i32) -> (i32, i32) {
+        let anchor = self.anchor.as_vec();
+        (
+            ((anchor.x + 0.5) * (columns - self.width) as f32).round() as i32,
+            ((0.5 - anchor.y) * (rows - self.height) as f32).round() as i32,
+        )
+    }
+}
+
+/// Entries to pick one from with the arrow keys or `W` and `S`.
+pub struct SelectList {
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+impl SelectList {
+    pub fn new(items: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            items: items.into_iter().map(Into::into).collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + 1) % self.items.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+        }
+    }
+
+    /// Moves the selection with the keys pressed this frame, returns the selected entry once
+    /// it is confirmed with enter.
+    pub fn handle(&mut self, keyboard_input: &Input<KeyCode>) -> Option<usize> {
+        if keyboard_input.any_just_pressed([KeyCode::Up, KeyCode::W]) {
+            self.select_previous();
+        }
+        if keyboard_input.any_just_pressed([KeyCode::Down, KeyCode::S]) {
+            self.select_next();
+        }
+        (keyboard_input.just_pressed(KeyCode::Return) && !self.items.is_empty())
+            .then_some(self.selected)
+    }
+}
+
+pub fn spawn_console_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: 1,
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            ..default()
+        },
+        CONSOLE_LAYER,
+        // Bevy's own UI is already drawn by the map camera.
+        UiCameraConfig { show_ui: false },
+    ));
+}
+
+/// Gives every new console the mesh it is drawn with, the same way [`Renderable`]s get their
+/// sprites.
+///
+/// [`Renderable`]: crate::common::components::Renderable
+pub fn spawn_console_meshes(
+    charset: Res<CharsetAsset>,
+    atlases: Res<Assets<TextureAtlas>>,
+    mut material: Local<Option<Handle<ColorMaterial>>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    consoles: Query<(Entity, &Console, Has<Visibility>), Added<Console>>,
+    mut commands: Commands,
+) {
+    let Some(atlas) = atlases.get(&charset.atlas) else {
+        return;
+    };
+    for (entity, console, has_visibility) in &consoles {
+        let material = material
+            .get_or_insert_with(|| materials.add(ColorMaterial::from(atlas.texture.clone())))
+            .clone();
+        let mut entity = commands.entity(entity);
+        entity.insert((
+            Mesh2dHandle(meshes.add(build_console_mesh(console, atlas))),
+            material,
+            TransformBundle::default(),
+            CONSOLE_LAYER,
+        ));
+        // Consoles that start out hidden keep their visibility.
+        if has_visibility {
+            entity.insert((InheritedVisibility::default(), ViewVisibility::default()));
+        } else {
+            entity.insert(VisibilityBundle::default());
+        }
+    }
+}
+
+pub fn update_console_meshes(
+    charset: Res<CharsetAsset>,
+    atlases: Res<Assets<TextureAtlas>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    consoles: Query<(&Console, &Mesh2dHandle), Changed<Console>>,
+) {
+    let Some(atlas) = atlases.get(&charset.atlas) else {
+        return;
+    };
+    for (console, handle) in &consoles {
+        if let Some(mesh) = meshes.get_mut(&handle.0) {
+            *mesh = build_console_mesh(console, atlas);
+        }
+    }
+}
+
+/// Keeps every console at its anchor as the window is resized, snapped to the cell grid.
+pub fn place_consoles(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut consoles: Query<(&Console, &mut Transform)>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let columns = (window.width() / CELL_SIZE) as i32;
+    let rows = (window.height() / CELL_SIZE) as i32;
+    for (console, mut transform) in &mut consoles {
+        let (x, y) = console.origin(columns, rows);
+        transform.translation = Vec3::new(
+            (-window.width() / 2.0 + x as f32 * CELL_SIZE).round(),
+            (window.height() / 2.0 - y as f32 * CELL_SIZE).round(),
+            RenderLayer::Overlay.z(),
+        );
+    }
+}
+
+/// Background and glyph quads for every cell, the console's top left corner at the origin.
+fn build_console_mesh(console: &Console, atlas: &TextureAtlas) -> Mesh {
+    let mut mesh = GlyphMesh::default();
+    let size = Vec2::splat(CELL_SIZE);
+    for y in 0..console.height {
+        for x in 0..console.width {
+            let Some(cell) = console.cell(x, y) else {
+                continue;
+            };
+            let center = Vec2::new(x as f32 + 0.5, -(y as f32 + 0.5)) * CELL_SIZE;
+            if let Some(bg) = cell.bg {
+                mesh.push(atlas, FULL_BLOCK, center, size, bg);
+            }
+            if cell.glyph != ' ' {
+                mesh.push(atlas, cp437::to_index(cell.glyph), center, size, cell.fg);
+            }
+        }
+    }
+    mesh.build()
+}
+
+#[test]
+fn test_console() {
+    let mut console = Console::new(10, 4, Anchor::BottomRight);
+    console.draw_box(0, 0, 10, 4, Color::WHITE, Some(Color::BLACK));
+    console.print(8, 1, "clipped", Color::RED);
+    assert_eq!(console.cell(0, 0).unwrap().glyph, '┌');
+    assert_eq!(console.cell(9, 3).unwrap().glyph, '┘');
+    assert_eq!(console.cell(9, 1).unwrap().glyph, 'l');
+    assert_eq!(console.cell(9, 1).unwrap().bg, Some(Color::BLACK));
+    assert_eq!(console.cell(10, 1), None);
+    assert_eq!(console.origin(80, 45), (70, 41));
+
+    let mut list = SelectList::new(["Resume", "Quit"]);
+    list.select_previous();
+    assert_eq!(list.selected, 1);
+    console.list(1, 1, &list, Color::WHITE, Color::YELLOW);
+    assert_eq!(console.cell(1, 2).unwrap().glyph, '►');
+}
@@ -0,0 +1,84 @@
+use bevy::{prelude::*, utils::HashSet, window::PrimaryWindow};
+
+use crate::{
+    common::{resources::HoveredTile, Vec2Int},
+    map_generator::Map,
+    player::{repeated_direction, InputSettings, KeyRepeat},
+    MainCamera,
+};
+
+pub fn pick_hovered_tile(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut hovered: ResMut<HoveredTile>,
+) {
+    let (Ok(window), Ok((camera, transform))) = (windows.get_single(), cameras.get_single()) else {
+        return;
+    };
+    // The camera transform carries both the scrolling and the zoom, so going through it
+    // undoes everything `Vec2Int::to_world` and the camera did to a tile.
+    hovered.0 = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(transform, cursor))
+        .map(Vec2Int::from_world);
+}
+
+/// Where a keyboard driven cursor on the map goes this frame, if anywhere. Looking and
+/// targeting both move their cursor this way: the movement keys step it, held keys repeat,
+/// and the next target key jumps between `targets`, which are only gathered when it is pressed.
+pub fn move_map_cursor(
+    cursor: Vec2Int,
+    keyboard_input: &Input<KeyCode>,
+    time: &Time,
+    settings: &InputSettings,
+    repeat: &mut KeyRepeat,
+    map: &Map,
+    targets: impl FnOnce() -> Vec<Vec2Int>,
+) -> Option<Vec2Int> {
+    if keyboard_input.just_pressed(settings.next_target) {
+        return next_target(&targets(), cursor);
+    }
+    let direction = repeated_direction(keyboard_input, time.elapsed_seconds(), settings, repeat)?;
+    Some(cursor + direction).filter(|cursor| map.in_bounds(*cursor))
+}
+
+/// Puts the tiles of monsters and items in the order the next target key visits them:
+/// monsters first, then items, the closest of each first. Every tile comes once.
+pub fn target_order(from: Vec2Int, targets: impl Iterator<Item = (Vec2Int, bool)>) -> Vec<Vec2Int> {
+    let mut targets: Vec<(Vec2Int, bool)> = targets.collect();
+    targets.sort_by_key(|(tile, monster)| {
+        (!monster, tile.octile_distance(&from, 2, 3), tile.y, tile.x)
+    });
+    let mut seen = HashSet::new();
+    targets
+        .into_iter()
+        .map(|(tile, _)| tile)
+        .filter(|tile| seen.insert(*tile))
+        .collect()
+}
+
+/// The target after the one under the cursor, or the first one.
+fn next_target(targets: &[Vec2Int], cursor: Vec2Int) -> Option<Vec2Int> {
+    let next = targets
+        .iter()
+        .position(|target| *target == cursor)
+        .map_or(0, |index| (index + 1) % targets.len());
+    targets.get(next).copied()
+}
+
+#[test]
+fn test_next_target() {
+    let targets = [Vec2Int::new(1, 1), Vec2Int::new(4, 2)];
+    assert_eq!(next_target(&targets, Vec2Int::new(0, 0)), Some(targets[0]));
+    assert_eq!(next_target(&targets, targets[0]), Some(targets[1]));
+    assert_eq!(next_target(&targets, targets[1]), Some(targets[0]));
+    assert_eq!(next_target(&[], Vec2Int::new(0, 0)), None);
+
+    let from = Vec2Int::new(0, 0);
+    let (near, far, item) = (Vec2Int::new(1, 0), Vec2Int::new(5, 0), Vec2Int::new(2, 0));
+    let order = target_order(
+        from,
+        [(item, false), (far, true), (near, true), (near, false)].into_iter(),
+    );
+    assert_eq!(order, [near, far, item]);
+}
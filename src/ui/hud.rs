@@ -0,0 +1,40 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{common::components::Health, player::Player};
+
+use super::console::Console;
+
+#[derive(Component)]
+pub struct Hud;
+
+pub fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        Name::from("Hud"),
+        Hud,
+        Console::new(16, 3, Anchor::BottomLeft),
+    ));
+}
+
+pub fn update_hud(
+    players: Query<&Health, (With<Player>, Changed<Health>)>,
+    mut huds: Query<&mut Console, With<Hud>>,
+) {
+    let Ok(health) = players.get_single() else {
+        return;
+    };
+    let color = if health.current * 3 <= health.max {
+        Color::RED
+    } else {
+        Color::WHITE
+    };
+    for mut console in &mut huds {
+        let (width, height) = (console.width, console.height);
+        console.draw_box(0, 0, width, height, Color::GRAY, Some(Color::BLACK));
+        console.print(
+            2,
+            1,
+            &format!("HP {}/{}", health.current, health.max),
+            color,
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{
+    common::{
+        components::{Description, Health, Position, RenderLayer, Renderable},
+        resources::InputFocus,
+        Vec2Int,
+    },
+    enemy::Enemy,
+    item::Item,
+    map_generator::{viewshed::MapVisibility, Map},
+    player::{InputSettings, KeyRepeat, Player},
+    trap::Trap,
+};
+
+use super::{
+    console::Console,
+    cursor::{move_map_cursor, target_order},
+};
+
+const PANEL_WIDTH: i32 = 44;
+const PANEL_HEIGHT: i32 = 9;
+
+/// Where the look cursor is while the map is being looked at.
+#[derive(Resource, Default)]
+pub struct Look {
+    pub cursor: Vec2Int,
+}
+
+/// Marks the looked at tile on the map.
+#[derive(Component)]
+pub struct LookCursor;
+
+/// Describes the looked at tile and everything on it.
+#[derive(Component)]
+pub struct LookPanel;
+
+pub fn spawn_look_panel(mut commands: Commands) {
+    commands.spawn((
+        Name::from("Look Panel"),
+        LookPanel,
+        Console::new(PANEL_WIDTH, PANEL_HEIGHT, Anchor::BottomCenter),
+        Visibility::Hidden,
+    ));
+}
+
+pub fn spawn_look_cursor(mut commands: Commands) {
+    commands
+        .spawn(Renderable {
+            glyph: ' ',
+            fg: Color::NONE,
+            bg: Some(Color::rgba(1.0, 1.0, 0.0, 0.35)),
+            layer: RenderLayer::Overlay,
+        })
+        .insert(Visibility::Hidden)
+        .insert(Name::from("Look Cursor"))
+        .insert(LookCursor)
+        .insert(Position(Vec2Int::new(0, 0)));
+}
+
+/// Everything that can be looked at on a tile.
+type Lookable = (
+    &'static Name,
+    &'static Position,
+    &'static Visibility,
+    Option<&'static Description>,
+    Option<&'static Health>,
+    Option<&'static Trap>,
+);
+
+/// Starts and stops looking and moves the look cursor. Enter stops looking and describes the
+/// looked at tile in the log. Looking never spends a turn, the player systems wait while the
+/// keyboard is busy here.
+pub fn look(
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    settings: Res<InputSettings>,
+    mut repeat: Local<KeyRepeat>,
+    mut focus: ResMut<InputFocus>,
+    mut look: ResMut<Look>,
+    map: Res<Map>,
+    fog: Res<MapVisibility>,
+    players: Query<&Position, With<Player>>,
+    targets: Query<(&Position, &Visibility, Has<Enemy>), Or<(With<Enemy>, With<Item>)>>,
+    entities: Query<Lookable, Without<LookCursor>>,
+) {
+    match *focus {
+        InputFocus::Game if keyboard_input.just_pressed(settings.look) => {
+            let Ok(player) = players.get_single() else {
+                return;
+            };
+            *focus = InputFocus::Look;
+            look.cursor = player.0;
+            return;
+        }
+        InputFocus::Look => {}
+        _ => return,
+    }
+
+    if keyboard_input.any_just_pressed([settings.look, KeyCode::Escape]) {
+        *focus = InputFocus::Game;
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        let mut seen: Vec<String> = look_at(&map, &fog, look.cursor, &entities)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        seen.push(tile_line(&map, &fog, look.cursor));
+        info!("{}", seen.join(", "));
+        *focus = InputFocus::Game;
+        return;
+    }
+    let cursor = look.cursor;
+    let next = move_map_cursor(
+        cursor,
+        &keyboard_input,
+        &time,
+        &settings,
+        &mut repeat,
+        &map,
+        || {
+            let from = players.get_single().map_or(cursor, |player| player.0);
+            target_order(
+                from,
+                targets
+                    .iter()
+                    .filter(|(position, shown, _)| {
+                        **shown != Visibility::Hidden && fog.is_visible(&map, position.0)
+                    })
+                    .map(|(position, _, monster)| (position.0, monster)),
+            )
+        },
+    );
+    if let Some(next) = next {
+        look.cursor = next;
+    }
+}
+
+/// Shows the look cursor and fills the panel whenever looking starts, stops or moves.
+pub fn show_look(
+    focus: Res<InputFocus>,
+    look: Res<Look>,
+    map: Res<Map>,
+    fog: Res<MapVisibility>,
+    entities: Query<Lookable, (Without<LookCursor>, Without<LookPanel>)>,
+    mut cursors: Query<(&mut Position, &mut Visibility), With<LookCursor>>,
+    mut panels: Query<(&mut Console, &mut Visibility), (With<LookPanel>, Without<LookCursor>)>,
+) {
+    if !focus.is_changed() && !look.is_changed() {
+        return;
+    }
+    let shown = if *focus == InputFocus::Look {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for (mut position, mut visibility) in &mut cursors {
+        position.0 = look.cursor;
+        *visibility = shown;
+    }
+    let Ok((mut console, mut visibility)) = panels.get_single_mut() else {
+        return;
+    };
+    *visibility = shown;
+    if shown == Visibility::Hidden {
+        return;
+    }
+
+    let mut lines = vec![tile_line(&map, &fog, look.cursor)];
+    for (name, description) in look_at(&map, &fog, look.cursor, &entities) {
+        lines.push(name);
+        if let Some(description) = description {
+            lines.extend(
+                wrap(description, PANEL_WIDTH as usize - 6).map(|line| format!("  {line}")),
+            );
+        }
+    }
+
+    let (width, height) = (console.width, console.height);
+    console.draw_box(0, 0, width, height, Color::GRAY, Some(Color::BLACK));
+    console.print(2, 0, " Look ", Color::WHITE);
+    for (row, line) in lines.iter().enumerate().take((height - 2) as usize) {
+        let color = if row == 0 { Color::GRAY } else { Color::WHITE };
+        console.print(2, row as i32 + 1, line, color);
+    }
+}
+
+/// Names what the player sees on `tile`, with a rough health for creatures and a description
+/// where there is one. Nothing is seen on tiles out of view.
+fn look_at<F: bevy::ecs::query::ReadOnlyWorldQuery>(
+    map: &Map,
+    fog: &MapVisibility,
+    tile: Vec2Int,
+    entities: &Query<Lookable, F>,
+) -> Vec<(String, Option<&'static str>)> {
+    if !fog.is_visible(map, tile) {
+        return Vec::new();
+    }
+    entities
+        .iter()
+        .filter(|(_, position, shown, _, _, trap)| {
+            position.0 == tile && **shown != Visibility::Hidden && !trap.is_some_and(Trap::hidden)
+        })
+        .map(|(name, _, _, description, health, _)| {
+            let name = match health {
+                Some(health) => format!("{} ({})", name, health_estimate(health)),
+                None => name.to_string(),
+            };
+            (name, description.map(|description| description.0))
+        })
+        .collect()
+}
+
+fn tile_line(map: &Map, fog: &MapVisibility, tile: Vec2Int) -> String {
+    if !fog.is_revealed(map, tile) {
+        return "Unexplored".to_string();
+    }
+    let description = map.tiles[map.xy_idx(tile.x, tile.y)]
+        .properties()
+        .description;
+    let seen = if fog.is_visible(map, tile) {
+        "in view"
+    } else {
+        "remembered"
+    };
+    format!("{description}, {seen}")
+}
+
+/// A rough idea of how hurt something is, without giving away the numbers.
+fn health_estimate(health: &Health) -> &'static str {
+    match health.current * 4 / health.max.max(1) {
+        4.. => "unhurt",
+        3 => "lightly wounded",
+        2 => "wounded",
+        1 => "badly wounded",
+        _ => "nearly dead",
+    }
+}
+
+/// Breaks `text` into lines of at most `width` characters at spaces.
+fn wrap(text: &str, width: usize) -> impl Iterator<Item = String> + '_ {
+    let mut words = text.split_whitespace().peekable();
+    std::iter::from_fn(move || {
+        let mut line = words.next()?.to_string();
+        while let Some(word) = words.next_if(|word| line.len() + 1 + word.len() <= width) {
+            line.push(' ');
+            line.push_str(word);
+        }
+        Some(line)
+    })
+}
+
+#[test]
+fn test_look() {
+    assert_eq!(
+        wrap("A stout miner who goes through walls", 16).collect::<Vec<_>>(),
+        ["A stout miner", "who goes through", "walls"]
+    );
+    assert_eq!(
+        health_estimate(&Health {
+            current: 10,
+            max: 10
+        }),
+        "unhurt"
+    );
+    assert_eq!(
+        health_estimate(&Health {
+            current: 1,
+            max: 10
+        }),
+        "nearly dead"
+    );
+}
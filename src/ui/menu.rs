@@ -0,0 +1,71 @@
+use bevy::{app::AppExit, prelude::*, sprite::Anchor};
+
+use crate::common::resources::InputFocus;
+
+use super::console::{Console, SelectList};
+
+const RESUME: usize = 0;
+
+/// Opened and closed with escape, the game waits while it is open.
+#[derive(Component)]
+pub struct PauseMenu(SelectList);
+
+pub fn spawn_pause_menu(mut commands: Commands) {
+    let list = SelectList::new(["Resume", "Quit"]);
+    let mut console = Console::new(14, list.items.len() as i32 + 4, Anchor::Center);
+    draw_pause_menu(&mut console, &list);
+    commands.spawn((
+        Name::from("Pause Menu"),
+        PauseMenu(list),
+        console,
+        Visibility::Hidden,
+    ));
+}
+
+pub fn pause_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut focus: ResMut<InputFocus>,
+    mut menus: Query<(&mut PauseMenu, &mut Console, &mut Visibility)>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Ok((mut menu, mut console, mut visibility)) = menus.get_single_mut() else {
+        return;
+    };
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        match *focus {
+            InputFocus::Menu => {
+                *visibility = Visibility::Hidden;
+                *focus = InputFocus::Game;
+            }
+            InputFocus::Game => {
+                *visibility = Visibility::Inherited;
+                *focus = InputFocus::Menu;
+                menu.0.selected = RESUME;
+                draw_pause_menu(&mut console, &menu.0);
+            }
+            // Escape leaves other modes before it opens the menu.
+            _ => {}
+        }
+        return;
+    }
+    if *focus != InputFocus::Menu {
+        return;
+    }
+    let selected = menu.0.selected;
+    match menu.0.handle(&keyboard_input) {
+        Some(RESUME) => {
+            *visibility = Visibility::Hidden;
+            *focus = InputFocus::Game;
+        }
+        Some(_) => exit.send(AppExit),
+        None if menu.0.selected != selected => draw_pause_menu(&mut console, &menu.0),
+        None => {}
+    }
+}
+
+fn draw_pause_menu(console: &mut Console, list: &SelectList) {
+    let (width, height) = (console.width, console.height);
+    console.draw_box(0, 0, width, height, Color::WHITE, Some(Color::BLACK));
+    console.print(2, 0, " Paused ", Color::WHITE);
+    console.list(2, 2, list, Color::GRAY, Color::YELLOW);
+}
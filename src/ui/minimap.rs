@@ -0,0 +1,200 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    common::{
+        components::{Position, Renderable},
+        Vec2Int,
+    },
+    item::Item,
+    map_generator::{viewshed::MapVisibility, Map, TileChanged},
+    player::Player,
+};
+
+/// Screen pixels per tile on the minimap.
+const MINIMAP_SCALE: f32 = 2.0;
+/// Brightness of remembered tiles compared to the ones in view.
+const REMEMBERED_SHADE: f32 = 0.5;
+const PLAYER_COLOR: Color = Color::YELLOW;
+
+/// One pixel per tile of the explored map, shared by the minimap and the overview.
+#[derive(Resource)]
+pub struct MinimapImage(Handle<Image>);
+
+#[derive(Component)]
+pub struct Minimap;
+
+/// The whole explored map across the screen, toggled with `M`.
+#[derive(Component)]
+pub struct Overview;
+
+pub fn spawn_minimap(map: Res<Map>, mut images: ResMut<Assets<Image>>, mut commands: Commands) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: map.width as u32,
+            height: map.height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+    commands.insert_resource(MinimapImage(image.clone()));
+    commands.spawn((
+        Name::from("Minimap"),
+        Minimap,
+        ImageBundle {
+            image: UiImage::new(image.clone()),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.0),
+                right: Val::Px(8.0),
+                width: Val::Px(map.width as f32 * MINIMAP_SCALE),
+                height: Val::Px(map.height as f32 * MINIMAP_SCALE),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            // Below the tooltip.
+            z_index: ZIndex::Global(-1),
+            ..default()
+        },
+    ));
+    commands
+        .spawn((
+            Name::from("Overview"),
+            Overview,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.9).into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(ImageBundle {
+                image: UiImage::new(image),
+                style: Style {
+                    max_width: Val::Percent(95.0),
+                    max_height: Val::Percent(95.0),
+                    height: Val::Percent(95.0),
+                    aspect_ratio: Some(map.width as f32 / map.height as f32),
+                    ..default()
+                },
+                ..default()
+            });
+        });
+}
+
+pub fn toggle_overview(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut overviews: Query<&mut Visibility, With<Overview>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::M) {
+        return;
+    }
+    for mut visibility in &mut overviews {
+        *visibility = if *visibility == Visibility::Hidden {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Paints the explored map again whenever the view or a tile changed or the player moved.
+pub fn update_minimap(
+    map: Res<Map>,
+    fog: Res<MapVisibility>,
+    mut changes: EventReader<TileChanged>,
+    minimap: Option<Res<MinimapImage>>,
+    players: Query<Ref<Position>, With<Player>>,
+    items: Query<(&Position, &Renderable), With<Item>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // Moving creatures take the map mutably every turn, so only the events tell what changed.
+    let tiles_changed = changes.read().count() > 0;
+    let Some(minimap) = minimap else {
+        return;
+    };
+    let player_moved = players.iter().any(|position| position.is_changed());
+    if !fog.is_changed() && !tiles_changed && !player_moved && !minimap.is_added() {
+        return;
+    }
+    let Some(image) = images.get_mut(&minimap.0) else {
+        return;
+    };
+    let markers = items
+        .iter()
+        .map(|(position, renderable)| (position.0, renderable.fg))
+        .chain(players.iter().map(|position| (position.0, PLAYER_COLOR)));
+    image.data = minimap_pixels(&map, &fog, markers);
+}
+
+/// RGBA pixels of the explored map with the top row first. Markers are only drawn on tiles
+/// the player has seen.
+fn minimap_pixels(
+    map: &Map,
+    fog: &MapVisibility,
+    markers: impl Iterator<Item = (Vec2Int, Color)>,
+) -> Vec<u8> {
+    let pixel = |position: Vec2Int| {
+        let row = map.height - 1 - position.y;
+        (row * map.width + position.x) as usize * 4
+    };
+    let mut pixels = vec![0; map.len() * 4];
+    for idx in fog.revealed.ones() {
+        let (x, y) = map.idx_xy(idx);
+        let color = map.tiles[idx].properties().fg;
+        let shade = if fog.visible.contains(idx) {
+            1.0
+        } else {
+            REMEMBERED_SHADE
+        };
+        let start = pixel(Vec2Int::new(x, y));
+        pixels[start..start + 4].copy_from_slice(
+            &Color::rgb(color.r() * shade, color.g() * shade, color.b() * shade).as_rgba_u8(),
+        );
+    }
+    for (position, color) in markers {
+        if fog.is_revealed(map, position) {
+            let start = pixel(position);
+            pixels[start..start + 4].copy_from_slice(&color.as_rgba_u8());
+        }
+    }
+    pixels
+}
+
+#[test]
+fn test_minimap_pixels() {
+    let (map, markers) = Map::from_ascii(
+        "
+        #####
+        #.@.#
+        #####",
+    )
+    .unwrap();
+    let player = markers[&'@'][0];
+    let mut fog = MapVisibility::new(&map);
+    fog.revealed.insert(map.xy_idx(player.x, player.y));
+    let hidden = Vec2Int::new(1, 1);
+    let pixels = minimap_pixels(
+        &map,
+        &fog,
+        [(player, Color::YELLOW), (hidden, Color::RED)].into_iter(),
+    );
+    let at = |position: Vec2Int| {
+        let start = ((map.height - 1 - position.y) * map.width + position.x) as usize * 4;
+        &pixels[start..start + 4]
+    };
+    assert_eq!(at(player), Color::YELLOW.as_rgba_u8());
+    assert_eq!(at(hidden), [0, 0, 0, 0]);
+}
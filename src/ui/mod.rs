@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+
+use crate::{
+    common::{
+        resources::{HoveredTile, InputFocus},
+        states::GameState,
+    },
+    map_generator::Map,
+};
+
+use self::{
+    console::{place_consoles, spawn_console_camera, spawn_console_meshes, update_console_meshes},
+    cursor::pick_hovered_tile,
+    hud::{spawn_hud, update_hud},
+    look::{look, show_look, spawn_look_cursor, spawn_look_panel, Look},
+    menu::spawn_pause_menu,
+    minimap::{spawn_minimap, toggle_overview, update_minimap},
+    tooltip::{spawn_tooltip, update_tooltip},
+};
+
+pub use self::{
+    cursor::{move_map_cursor, target_order},
+    menu::pause_menu,
+};
+
+pub mod console;
+mod cursor;
+mod hud;
+mod look;
+mod menu;
+mod minimap;
+mod tooltip;
+
+pub struct InterfacePlugin;
+
+impl Plugin for InterfacePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HoveredTile>()
+            .init_resource::<InputFocus>()
+            .init_resource::<Look>()
+            .add_systems(
+                Startup,
+                (
+                    spawn_tooltip,
+                    spawn_console_camera,
+                    spawn_hud,
+                    spawn_pause_menu,
+                    spawn_look_panel,
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::Setup),
+                (spawn_look_cursor, spawn_minimap),
+            )
+            .add_systems(PreUpdate, pick_hovered_tile)
+            .add_systems(
+                Update,
+                (
+                    update_tooltip,
+                    update_hud,
+                    // Escape leaves look mode before it can open the menu.
+                    (
+                        pause_menu,
+                        (look, show_look).run_if(resource_exists::<Map>()),
+                    )
+                        .chain(),
+                    toggle_overview,
+                    update_minimap.run_if(resource_exists::<Map>()),
+                    (spawn_console_meshes, update_console_meshes, place_consoles),
+                ),
+            );
+    }
+}
@@ -0,0 +1,86 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    common::{components::Position, resources::HoveredTile},
+    map_generator::{viewshed::MapVisibility, Map},
+    targeting::TargetMarker,
+    trap::Trap,
+};
+
+use super::look::LookCursor;
+
+const CURSOR_OFFSET: f32 = 16.0;
+
+#[derive(Component)]
+pub struct Tooltip;
+
+pub fn spawn_tooltip(mut commands: Commands) {
+    commands.spawn((
+        Name::from("Tooltip"),
+        Tooltip,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(4.0)),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.8)),
+    ));
+}
+
+/// Names whatever is under the cursor: visible creatures first, then the remembered tile.
+pub fn update_tooltip(
+    hovered: Res<HoveredTile>,
+    map: Option<Res<Map>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    fog: Option<Res<MapVisibility>>,
+    entities: Query<
+        (&Name, &Position, &Visibility, Option<&Trap>),
+        (Without<Tooltip>, Without<LookCursor>, Without<TargetMarker>),
+    >,
+    mut tooltips: Query<(&mut Text, &mut Style, &mut Visibility), With<Tooltip>>,
+) {
+    let Ok((mut text, mut style, mut visibility)) = tooltips.get_single_mut() else {
+        return;
+    };
+    *visibility = Visibility::Hidden;
+
+    let (Some(map), Some(fog), Some(tile), Ok(window)) =
+        (map, fog, hovered.0, windows.get_single())
+    else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    if !fog.is_revealed(&map, tile) {
+        return;
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    if fog.is_visible(&map, tile) {
+        lines.extend(
+            entities
+                .iter()
+                .filter(|(_, position, shown, trap)| {
+                    position.0 == tile
+                        && **shown != Visibility::Hidden
+                        && !trap.is_some_and(Trap::hidden)
+                })
+                .map(|(name, ..)| name.to_string()),
+        );
+    }
+    lines.push(map.tiles[map.xy_idx(tile.x, tile.y)].properties().description.to_string());
+
+    text.sections[0].value = lines.join("\n");
+    style.left = Val::Px(cursor.x + CURSOR_OFFSET);
+    style.top = Val::Px(cursor.y + CURSOR_OFFSET);
+    *visibility = Visibility::Inherited;
+}